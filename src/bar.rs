@@ -1,65 +1,98 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
 
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use swaybar_types::{Button, Click};
+use tokio::process::Command;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{error, warn};
 
 use crate::{
+    click::{run_action, ClickActions},
     config::Config,
     err::Res as Result,
-    nine::{NineCmd, Position},
+    format,
+    nine::{Nine, NineCmd, Position, Split, Workspace, WorkspaceState},
+    output::{self, Output},
+    rpc::Steps,
+    rule::Rule,
+    signal::{Pause, Paused},
+    sway,
+    widget::{self, Factory, Registry, Value, Values, Widget},
 };
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(untagged)]
-enum StringOrU32 {
+pub enum StringOrU32 {
     String(String),
     U32(u32),
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Block {
     #[serde(skip_serializing)]
-    widget: Option<String>,
+    pub widget: Option<String>,
     #[serde(skip_serializing)]
-    char_width: Option<usize>,
-    //#[serde(skip_serializing)]
-    //format: Option<String>,
-    full_text: Option<String>,
+    pub char_width: Option<usize>,
+    #[serde(skip_serializing)]
+    pub format: Option<String>,
+    #[serde(skip_serializing)]
+    pub on_click: Option<ClickActions>,
+    #[serde(skip_serializing)]
+    pub rules: Option<Vec<Rule>>,
+    #[serde(skip_serializing)]
+    pub interval: Option<f64>,
+    #[serde(skip_serializing)]
+    pub split: Option<Split>,
+    pub full_text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    short_text: Option<String>,
+    pub short_text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    background: Option<String>,
+    pub background: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    separator_block_width: Option<u32>,
+    pub separator_block_width: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    min_width: Option<StringOrU32>,
+    pub min_width: Option<StringOrU32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    align: Option<Align>,
+    pub align: Option<Align>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    color: Option<String>,
+    pub color: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    border: Option<String>,
+    pub border: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    border_top: Option<u32>,
+    pub border_top: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    border_bottom: Option<u32>,
+    pub border_bottom: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    border_left: Option<u32>,
+    pub border_left: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    border_right: Option<u32>,
+    pub border_right: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>,
+    pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    instance: Option<String>,
+    pub instance: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    urgent: Option<bool>,
+    pub urgent: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    separator: Option<bool>,
+    pub separator: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    markup: Option<String>,
+    pub markup: Option<String>,
 }
 
 impl Block {
+    /// Seconds between widget updates, if set to a sane value.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+            .filter(|secs| *secs > 0.0)
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    }
+
     fn load_defaults(&mut self, block: &Block) {
         if block.background.is_some() && self.background.is_none() {
             self.background = block.background.clone();
@@ -102,7 +135,7 @@ impl Block {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
-enum Align {
+pub enum Align {
     Left,
     Center,
     Right,
@@ -110,174 +143,531 @@ enum Align {
 
 #[derive(Debug)]
 pub enum Update {
-    BatteryCapacity(Option<String>),
-    BatteryStatus(Option<String>),
-    Brightness(Option<u32>),
+    Click(Click),
     Config(Box<Config>),
+    /// The config file couldn't be loaded
+    ConfigError(String),
     Redraw,
-    Time(Option<String>),
-    Volume(Option<u32>),
-    Mute(Option<bool>),
-    WindowName(Option<String>),
+    Stop,
+    Cont,
     Nine(NineCmd),
+    /// Move the focused window to a neighboring cell of the grid
+    Carry(NineCmd),
+    /// Swap the windows on the focused workspace with a neighboring cell's
+    Swap(NineCmd),
+    /// What's on each sway workspace
+    Workspaces(Vec<WorkspaceState>),
+    /// Switch to a profile from the config, or back to the startup layout
+    Profile(Option<String>),
+    /// New values for the widget with the given name
+    Widget(String, Values),
+}
+
+impl Update {
+    pub fn widget<const N: usize>(name: &str, values: [(&str, Value); N]) -> Self {
+        Update::Widget(name.to_string(), widget::values(values))
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct Bar {
-    pub battery_status: Option<String>,
-    pub battery_capacity: Option<String>,
-    pub brightness: Option<u32>,
-    pub window_name: Option<String>,
-    pub time: Option<String>,
-    pub volume: Option<u32>,
-    pub mute: Option<bool>,
     config: Config,
-    nine: Position,
+    nine: Nine,
+    pause: Pause,
+    stopped: bool,
+    registry: Registry,
+    widgets: HashMap<String, Box<dyn Widget>>,
+    watchers: HashMap<String, (Option<Duration>, JoinHandle<()>)>,
+    tx: Option<mpsc::WeakUnboundedSender<Update>>,
+    output: Output,
+    config_error: Option<String>,
+    profile: Option<String>,
+    steps: Option<watch::Sender<Steps>>,
 }
 
 impl Bar {
-    pub fn new() -> Self {
-        Self::default()
+    /// Create a bar that spawns widget watchers feeding into `tx`.
+    pub fn new(tx: &mpsc::UnboundedSender<Update>) -> Self {
+        Self {
+            tx: Some(tx.downgrade()),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    /// Show the nine grid of this sway output, instead of the focused one.
+    pub fn set_bar_output(&mut self, output: Option<String>) {
+        self.nine.set_bar_output(output);
+    }
+
+    /// Save grid positions to this file, starting from the ones saved before.
+    pub fn set_state_file(&mut self, path: PathBuf) {
+        self.nine.set_state_file(path);
+    }
+
+    /// Channel to keep the RPC step sizes in line with the config.
+    pub fn set_steps(&mut self, steps: watch::Sender<Steps>) {
+        self.steps = Some(steps);
+    }
+
+    /// Widgets available to `[[bar]]` entries, instead of the builtin ones.
+    pub fn set_registry(&mut self, registry: Registry) {
+        self.registry = registry;
+    }
+
+    /// Make a widget available to `[[bar]]` entries under `name`.
+    pub fn register(&mut self, name: &str, factory: Factory) {
+        self.registry.register(name, factory);
+    }
+
+    /// Handle for watchers to wait on while the bar is hidden.
+    pub fn paused(&self) -> Paused {
+        self.pause.subscribe()
     }
 
-    pub async fn write_json(
+    fn redraw(&mut self, writer: &mut dyn Write) {
+        // Swaybar isn't reading while the bar is hidden
+        if self.stopped {
+            return;
+        }
+        let blocks = self.render_blocks();
+        if let Err(err) = self.output.write_blocks(writer, &blocks) {
+            error!("Could not write bar: {:?}", err);
+        }
+    }
+
+    /// Redraw the bar in its output format on every update, for good.
+    pub async fn write_bar(
         &mut self,
         writer: &mut dyn Write,
         mut rx_updates: mpsc::UnboundedReceiver<Update>,
     ) {
-        let mut sway = swayipc_async::Connection::new().await.unwrap();
-
-        // Start on TopLeft workspace number (not zero)
-        #[cfg(not(test))]
-        sway.run_command(format!("workspace number {}", 2))
-            .await
-            .unwrap();
+        // Without sway, e.g. in tmux, the bar still shows but can't move
+        let mut sway = match sway::connect().await {
+            Ok(sway) => Some(sway),
+            Err(err) => {
+                warn!("Not running grid commands: {:?}", err);
+                None
+            }
+        };
+        let mut started = false;
 
         while let Some(cmd) = rx_updates.recv().await {
+            // Go to the start cell once the config tells where it is
+            if !started && matches!(cmd, Update::Config(_) | Update::ConfigError(_)) {
+                started = true;
+                #[cfg(not(test))]
+                self.start(&mut sway).await;
+            }
             match cmd {
-                Update::Redraw => {
-                    writeln!(writer, "{},", self.to_json().unwrap()).unwrap();
-                    writer.flush().unwrap();
+                Update::Redraw => self.redraw(writer),
+                Update::Config(val) => {
+                    let errors = val.validate(&self.registry);
+                    if errors.is_empty() {
+                        self.config_error = None;
+                        self.load_config(*val);
+                    } else {
+                        for err in &errors {
+                            error!("Bad config: {}", err);
+                        }
+                        self.config_error = Some(errors.join("; "));
+                    }
                 }
-                Update::BatteryCapacity(val) => self.battery_capacity = val,
-                Update::BatteryStatus(val) => self.battery_status = val,
-                Update::Brightness(val) => self.brightness = val,
-                Update::Config(val) => self.config = *val,
-                Update::Time(val) => self.time = val,
-                Update::Mute(val) => self.mute = val,
-                Update::Volume(val) => self.volume = val,
-                Update::WindowName(val) => self.window_name = val,
+                Update::ConfigError(err) => self.config_error = Some(err),
+                Update::Widget(name, values) => self.update_widget(&name, values),
+                Update::Stop => {
+                    self.stopped = true;
+                    self.pause.set(true);
+                }
+                Update::Cont => {
+                    self.stopped = false;
+                    self.pause.set(false);
+                    self.redraw(writer);
+                }
+                Update::Click(click) => self.handle_click(&click),
+                Update::Profile(profile) => self.set_profile(profile),
                 Update::Nine(cmd) => {
-                    if let NineCmd::MovedTo(num) = cmd {
-                        self.nine = Position::from(num);
-                        writeln!(writer, "{},", self.to_json().unwrap()).unwrap();
-                        writer.flush().unwrap();
-                        continue;
+                    let moved = matches!(cmd, NineCmd::MovedTo(_));
+                    if let NineCmd::MovedTo(ref workspace) = cmd {
+                        if let Some(exec) = self.nine.exec_on_enter(workspace) {
+                            self.launch(&workspace.name, exec);
+                        }
                     }
-                    self.nine = self.nine.map_cmd(cmd);
-                    //sway.run_command(format!(
-                    //    "workspace {} {}",
-                    //    self.nine.num(),
-                    //    self.nine.name()
-                    //))
-                    sway.run_command(format!("workspace number {}", self.nine.num()))
-                        .await
-                        .unwrap();
+                    let command = self.nine.go(cmd);
+                    self.update_nine();
+                    run_commands(&mut sway, command).await;
+                    if moved {
+                        self.redraw(writer);
+                    }
+                }
+                Update::Workspaces(workspaces) => {
+                    self.nine.set_workspaces(workspaces);
+                    self.update_nine();
+                }
+                Update::Carry(cmd) => {
+                    let command = self.nine.carry(&cmd);
+                    self.update_nine();
+                    run_commands(&mut sway, command).await;
+                }
+                Update::Swap(cmd) => {
+                    let command = self.nine.swap(&cmd);
+                    self.update_nine();
+                    run_commands(&mut sway, command).await;
                 }
             }
         }
     }
 
-    pub fn to_json(&mut self) -> Result<String> {
-        let mut result = Vec::<String>::new();
-
-        for i in 0..self.config.bar.len() {
-            let mut block = self.config.bar[i].borrow_mut();
-            match block.widget.as_ref().unwrap().as_str() {
-                "time" => {
-                    if self.time.is_some() {
-                        block.full_text = Some(String::from(self.time.as_ref().unwrap()));
-                    }
-                }
-                "brightness" => {
-                    if self.brightness.is_some() {
-                        block.full_text = Some(format!("{:>2}{}", self.brightness.unwrap(), "🔅",));
-                    }
-                }
-                "battery" => {
-                    if self.battery_capacity.is_some() {
-                        block.full_text = Some(format!(
-                            "{}{}",
-                            self.battery_capacity.as_ref().unwrap(),
-                            match self.battery_status {
-                                Some(ref val) => match val.as_str() {
-                                    "Full" | "Charging" => "🔌",
-                                    "Discharging" => "🔋",
-                                    _ => "n/a ",
-                                },
-                                None => "n/a ",
-                            },
-                        ));
+    /// Go to the saved or configured cell of the focused output, or just
+    /// follow the focused workspace without `jump`.
+    #[cfg_attr(test, allow(dead_code))]
+    async fn start(&mut self, sway: &mut Option<swayipc_async::Connection>) {
+        let workspaces = match sway {
+            Some(sway) => sway.get_workspaces().await,
+            None => return,
+        };
+        let workspace = match workspaces {
+            Ok(workspaces) => workspaces.into_iter().find(|w| w.focused),
+            Err(_) => None,
+        };
+        let workspace = match workspace {
+            Some(workspace) => Workspace {
+                num: Some(workspace.num).filter(|num| *num >= 0),
+                name: workspace.name,
+                output: workspace.output,
+            },
+            None => return,
+        };
+
+        let command = self.nine.start(workspace);
+        self.update_nine();
+        run_commands(sway, command).await;
+    }
+
+    fn load_config(&mut self, config: Config) {
+        self.config = config;
+        self.nine
+            .load_config(&self.config.nine, &self.config.minimap);
+        if let Some(ref steps) = self.steps {
+            steps.send_replace(self.config.steps.clone());
+        }
+
+        // Keep a profile picked at runtime while the config still has it
+        if let Some(ref profile) = self.profile {
+            if !self.config.profiles.contains_key(profile) {
+                self.profile = None;
+            }
+        }
+
+        self.load_layout();
+    }
+
+    fn set_profile(&mut self, profile: Option<String>) {
+        if let Some(ref name) = profile {
+            if !self.config.profiles.contains_key(name) {
+                warn!("Unknown profile: {}", name);
+                return;
+            }
+        }
+        self.profile = profile;
+        self.load_layout();
+    }
+
+    /// The `default` block and `bar` entries of the current profile.
+    fn layout(&self) -> (&RefCell<Block>, &[RefCell<Block>]) {
+        self.config.layout(self.profile.as_deref())
+    }
+
+    /// Start the widgets of the current layout and stop the rest.
+    fn load_layout(&mut self) {
+        // Widgets poll at the first interval set by any of their blocks
+        let mut intervals: HashMap<String, Option<Duration>> = HashMap::new();
+        for block in self.layout().1 {
+            let block = block.borrow();
+            if let Some(ref name) = block.widget {
+                let interval = intervals.entry(name.clone()).or_default();
+                *interval = interval.or_else(|| block.interval());
+            }
+        }
+
+        // Stop watchers of widgets that are no longer in the bar, and restart
+        // the ones with a new interval
+        self.watchers.retain(|name, (interval, watcher)| {
+            let keep = intervals.get(name) == Some(interval);
+            if !keep {
+                watcher.abort();
+            }
+            keep
+        });
+
+        for (name, interval) in intervals {
+            if !self.widgets.contains_key(&name) {
+                match self.registry.create(&name) {
+                    Some(widget) => {
+                        self.widgets.insert(name.clone(), widget);
                     }
-                }
-                "window_name" => {
-                    if self.window_name.is_some() {
-                        let window_name = String::from(self.window_name.as_ref().unwrap());
-                        let max_chars = block.char_width.unwrap_or(100);
-                        let short_window_name = truncate(&window_name, max_chars);
-                        let short_window_name = format!("{}*", short_window_name);
-                        block.full_text = Some(window_name);
-                        block.short_text = Some(short_window_name);
+                    None => {
+                        warn!("Unknown widget: {}", name);
+                        continue;
                     }
                 }
-                "volume" => {
-                    if let Some(ref v) = self.volume {
-                        let pct = v * 100 / 65536;
-                        block.full_text = Some(format!(
-                            "{:>2}{}",
-                            pct,
-                            match self.mute {
-                                // TODO test missing fields..
-                                Some(mute) => match mute {
-                                    true => "🔇",
-                                    false => "🔈",
-                                },
-                                None => "🔈",
-                            },
-                        ));
-                    }
+            }
+            if !self.watchers.contains_key(&name) {
+                self.spawn_watcher(&name, interval);
+            }
+        }
+
+        self.update_nine();
+    }
+
+    /// Queue an update for the bar itself.
+    fn send(&self, update: Update) {
+        if let Some(tx) = self.tx.as_ref().and_then(|tx| tx.upgrade()) {
+            let _ = tx.send(update);
+        }
+    }
+
+    /// Run the `exec` of a cell in the background, letting the grid run it
+    /// again if it fails.
+    fn launch(&self, workspace: &str, exec: String) {
+        let tx = self.tx.clone();
+        let workspace = workspace.to_string();
+        tokio::spawn(async move {
+            let status = Command::new("sh")
+                .arg("-c")
+                .arg(&exec)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .status()
+                .await;
+            match status {
+                Ok(status) if status.success() => return,
+                Ok(status) => error!("{:?} on workspace {} failed: {}", exec, workspace, status),
+                Err(err) => error!("Could not run {:?}: {:?}", exec, err),
+            }
+            if let Some(tx) = tx.and_then(|tx| tx.upgrade()) {
+                let _ = tx.send(Update::Nine(NineCmd::ExecFailed(workspace)));
+            }
+        });
+    }
+
+    fn spawn_watcher(&mut self, name: &str, interval: Option<Duration>) {
+        let tx = match self.tx.as_ref().and_then(|tx| tx.upgrade()) {
+            Some(tx) => tx,
+            None => return,
+        };
+        let watcher = self.widgets[name].watch(name, tx, self.paused(), interval);
+
+        if let Some(watcher) = watcher {
+            let name_ = name.to_string();
+            let handle = tokio::spawn(async move {
+                if let Err(err) = watcher.await {
+                    error!("Widget {} failed: {:?}", name_, err);
                 }
-                "nine" => {
-                    block.full_text = Some(self.nine.to_string());
+            });
+            self.watchers.insert(name.to_string(), (interval, handle));
+        }
+    }
+
+    fn update_widget(&mut self, name: &str, values: Values) {
+        if let Some(widget) = self.widgets.get_mut(name) {
+            widget.update(values);
+        }
+    }
+
+    fn update_nine(&mut self) {
+        let values = self.nine.values();
+        let map = self.nine.minimap();
+        self.update_widget("nine", values);
+        self.update_widget("minimap", widget::values([("map", map.into())]));
+    }
+
+    fn handle_click(&self, click: &Click) {
+        // Blocks are named by widget with their index in the bar as instance,
+        // followed by the row and column for the cells of a split nine widget
+        let mut instance = click.instance.as_deref().unwrap_or_default().split(':');
+        let block = instance
+            .next()
+            .and_then(|i| i.parse::<usize>().ok())
+            .and_then(|i| self.layout().1.get(i));
+        let cell = instance
+            .map(|i| i.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+
+        if let Some(block) = block {
+            let block = block.borrow();
+            if block.widget != click.name {
+                return;
+            }
+            if let ([Some(row), Some(col)], Button::Left) = (cell.as_slice(), click.button) {
+                let position = Position {
+                    row: *row,
+                    col: *col,
+                    page: 0,
+                };
+                let output = self.nine.shown_output().to_string();
+                self.send(Update::Nine(NineCmd::GoToOn(output, position)));
+                self.send(Update::Redraw);
+                return;
+            }
+            if let Some(action) = block.on_click.as_ref().and_then(|a| a.get(click.button)) {
+                run_action(action);
+            }
+        }
+    }
+
+    pub fn to_json(&mut self) -> Result<String> {
+        let blocks = self.render_blocks();
+        Ok(output::to_json(blocks.iter().map(|(block, _)| block))?)
+    }
+
+    /// Current blocks, with the widget values they were rendered from.
+    pub fn render_blocks(&self) -> Vec<(Block, Values)> {
+        let mut result = Vec::new();
+        let (default, bar) = self.layout();
+
+        if let Some(ref err) = self.config_error {
+            let mut block = Block {
+                widget: Some("config_error".into()),
+                name: Some("config_error".into()),
+                full_text: Some(format!("config error: {}", err)),
+                color: Some("#ff0000".into()),
+                urgent: Some(true),
+                ..Default::default()
+            };
+            block.load_defaults(&default.borrow());
+            result.push((block, Values::new()));
+        }
+
+        for (i, block) in bar.iter().enumerate() {
+            // Render a copy so that rules only apply while they match
+            let mut block = block.borrow().clone();
+            let name = block.widget.clone().unwrap_or_default();
+            let mut values = Values::new();
+            if let Some(widget) = self.widgets.get(&name) {
+                widget.render(&mut block);
+                values = widget.values();
+                for rule in block.rules.clone().iter().flatten() {
+                    rule.apply(&values, &mut block);
                 }
-                _ => {}
             }
-            block.load_defaults(&self.config.default.borrow());
-            drop(block);
-            let block = &self.config.bar[i];
-            result.push(serde_json::to_string(&block).unwrap());
+            if let (Some(split), "nine") = (block.split, name.as_str()) {
+                result.extend(self.split_nine(i, &block, split, &values, &default.borrow()));
+                continue;
+            }
+            block.name = block.widget.clone();
+            block.instance = Some(i.to_string());
+            block.load_defaults(&default.borrow());
+            result.push((block, values));
         }
 
-        Ok(format!("[{}]", result.join(",")))
+        result
+    }
+
+    /// The nine widget as a block per column or cell, for the `i`th block of
+    /// the bar.  Each has the widget's values, with the text of the cell as
+    /// `cell` and whether we're on it as `current`, and sits right next to
+    /// the others unless the block sets its own separators.
+    fn split_nine(
+        &self,
+        i: usize,
+        block: &Block,
+        split: Split,
+        values: &Values,
+        default: &Block,
+    ) -> Vec<(Block, Values)> {
+        let cells: Vec<_> = self
+            .nine
+            .split(split)
+            .into_iter()
+            .map(|(position, text, current)| {
+                let mut values = values.clone();
+                values.insert("cell".into(), text.into());
+                values.insert("current".into(), current.into());
+                (position, values)
+            })
+            .collect();
+
+        let last = cells.len().saturating_sub(1);
+        cells
+            .into_iter()
+            .enumerate()
+            .map(|(n, (position, values))| {
+                let mut block = block.clone();
+                let template = block.format.as_deref().unwrap_or("{cell}");
+                block.full_text = Some(format::render(template, &values));
+                for rule in block.rules.clone().iter().flatten() {
+                    rule.apply(&values, &mut block);
+                }
+                if n < last {
+                    block.separator = block.separator.or(Some(false));
+                    block.separator_block_width = block.separator_block_width.or(Some(0));
+                }
+                block.name = block.widget.clone();
+                block.instance = Some(format!("{}:{}:{}", i, position.row, position.col));
+                block.load_defaults(default);
+                (block, values)
+            })
+            .collect()
     }
 }
 
-fn truncate(s: &str, max_chars: usize) -> &str {
-    match s.char_indices().nth(max_chars) {
-        None => s,
-        Some((idx, _)) => &s[..idx],
+/// Run sway commands from the nine grid, if there's a sway to run them.
+async fn run_commands(
+    sway: &mut Option<swayipc_async::Connection>,
+    commands: impl IntoIterator<Item = String>,
+) {
+    let sway = match sway {
+        Some(sway) => sway,
+        None => return,
+    };
+    for command in commands {
+        match sway.run_command(&command).await {
+            Ok(outcomes) => {
+                for err in outcomes.into_iter().filter_map(|outcome| outcome.err()) {
+                    error!("Sway command {:?} failed: {:?}", command, err);
+                }
+            }
+            Err(err) => error!("Could not run sway command {:?}: {:?}", command, err),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::prelude::*;
     use serde_json::{Result, Value};
     use swaybar_types::Block;
     use tokio::sync::mpsc;
 
+    use tokio::sync::watch;
+    use tokio::time::{timeout, Duration};
+
+    use swaybar_types::Click;
+
     use super::{Bar, Update};
 
-    use crate::config::Config;
+    use crate::{
+        config::Config,
+        nine::{NineCmd, Position, Split, Workspace},
+        rpc::Steps,
+        widget::{values, Values, Widget},
+    };
+
+    fn at(row: usize, col: usize) -> Position {
+        Position { row, col, page: 0 }
+    }
+
+    /// A bar loaded with the config in `toml`.
+    fn bar_from(toml: &str) -> Bar {
+        let config: Config = toml::from_str(toml).unwrap();
+        let mut bar = Bar::default();
+        bar.load_config(config);
+        bar
+    }
 
     //static CONFIG: &str = "[default]\n[[bar]]\nwidget = \"time\"";
 
@@ -291,31 +681,32 @@ mod tests {
 
     #[test]
     fn json_output_full() -> Result<()> {
-        let config: Config = toml::from_str(concat!(
+        let mut d = bar_from(concat!(
             "[default]\n",
             "[[bar]]\n",
             "widget = \"brightness\"\n",
             "[[bar]]\n",
             "widget = \"battery\"\n",
+            "format = \"{capacity}% {status}\"\n",
+            "rules = [{ when = \"capacity < 15\", color = \"#ff0000\", urgent = true }]\n",
             "[[bar]]\n",
             "widget = \"window_name\"\n",
             "[[bar]]\n",
             "widget = \"volume\"\n",
             "[[bar]]\n",
             "widget = \"time\"\n",
-        ))
-        .unwrap();
-        let mut d = Bar {
-            battery_status: Some("Full".into()),
-            battery_capacity: Some("99".into()),
-            brightness: Some(1_000),
-            window_name: Some("Window".into()),
-            time: Some("12:01".into()),
-            volume: Some(22_000),
-            mute: Some(false),
-            config,
-            ..Default::default()
-        };
+        ));
+        d.update_widget(
+            "battery",
+            values([("status", "Full".into()), ("capacity", 99.into())]),
+        );
+        d.update_widget("brightness", values([("percent", 1_000.into())]));
+        d.update_widget("window_name", values([("title", "Window".into())]));
+        d.update_widget("time", values([("timestamp", 0.into())]));
+        d.update_widget(
+            "volume",
+            values([("volume", 22_000.into()), ("mute", false.into())]),
+        );
         let j = d.to_json().unwrap();
         assert!(j.len() > 2);
 
@@ -324,45 +715,262 @@ mod tests {
         let first = &json[0];
         assert!(&first["full_text"].as_str().unwrap().starts_with("1000"));
 
+        // Widgets fill in their format
+        assert_eq!("99% Full", json[1]["full_text"]);
+        assert_eq!("33🔈", json[3]["full_text"]);
+
+        // Rules only apply while they match
+        assert!(json[1].get("color").is_none());
+        d.update_widget("battery", values([("capacity", 9.into())]));
+        let json: Value = serde_json::from_str(&d.to_json().unwrap())?;
+        assert_eq!("#ff0000", json[1]["color"]);
+        assert_eq!(true, json[1]["urgent"]);
+        d.update_widget("battery", values([("capacity", 60.into())]));
+        let json: Value = serde_json::from_str(&d.to_json().unwrap())?;
+        assert!(json[1].get("urgent").is_none());
+
+        // Blocks are addressable by click events
+        assert_eq!("brightness", first["name"]);
+        assert_eq!("0", first["instance"]);
+        assert_eq!("4", json[4]["instance"]);
+
         Ok(())
     }
 
+    #[derive(Debug, Default)]
+    struct Greeting(Values);
+
+    impl Widget for Greeting {
+        fn update(&mut self, values: Values) {
+            self.0.extend(values);
+        }
+
+        fn values(&self) -> Values {
+            self.0.clone()
+        }
+
+        fn default_format(&self) -> &str {
+            "hello {name}"
+        }
+    }
+
+    #[test]
+    fn custom_widget_from_registry() {
+        let config: Config = toml::from_str("[default]\n[[bar]]\nwidget = \"greeting\"").unwrap();
+        let mut bar = Bar::default();
+        bar.register("greeting", || Box::<Greeting>::default());
+        bar.load_config(config);
+        bar.update_widget("greeting", values([("name", "world".into())]));
+
+        let json: Value = serde_json::from_str(&bar.to_json().unwrap()).unwrap();
+        assert_eq!("hello world", json[0]["full_text"]);
+    }
+
+    #[test]
+    fn config_error_block() {
+        let mut bar = bar_from("[default]\n[[bar]]\nwidget = \"nine\"");
+        bar.config_error = Some("barnine.toml:3:1: oops".into());
+
+        let json: Value = serde_json::from_str(&bar.to_json().unwrap()).unwrap();
+        assert_eq!("config error: barnine.toml:3:1: oops", json[0]["full_text"]);
+        assert_eq!(true, json[0]["urgent"]);
+        assert_eq!("nine", json[1]["name"]);
+        assert_eq!("0", json[1]["instance"]);
+    }
+
+    #[test]
+    fn switch_profiles() {
+        let mut bar = bar_from(concat!(
+            "profile = \"docked\"\n",
+            "[[bar]]\n",
+            "widget = \"time\"\n",
+            "[profiles.docked.default]\n",
+            "color = \"#00ff00\"\n",
+            "[[profiles.docked.bar]]\n",
+            "widget = \"nine\"\n",
+            "[[profiles.docked.bar]]\n",
+            "widget = \"time\"\n",
+        ));
+
+        let names = |bar: &mut Bar| {
+            let json: Value = serde_json::from_str(&bar.to_json().unwrap()).unwrap();
+            json.as_array()
+                .unwrap()
+                .iter()
+                .map(|block| block["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Starts with the configured profile
+        assert_eq!(vec!["nine", "time"], names(&mut bar));
+        assert!(bar.widgets.contains_key("nine"));
+
+        // Unknown profiles are ignored
+        bar.set_profile(Some("nope".into()));
+        assert_eq!(vec!["nine", "time"], names(&mut bar));
+
+        // Back to the top-level layout while the config doesn't pick a profile
+        bar.config.profile = None;
+        bar.set_profile(None);
+        assert_eq!(vec!["time"], names(&mut bar));
+
+        bar.set_profile(Some("docked".into()));
+        let json: Value = serde_json::from_str(&bar.to_json().unwrap()).unwrap();
+        assert_eq!("#00ff00", json[0]["color"]);
+    }
+
+    #[tokio::test]
+    async fn reload_intervals_and_steps() {
+        let config = |toml: &str| -> Config { toml::from_str(toml).unwrap() };
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (tx_steps, rx_steps) = watch::channel(Steps::default());
+        let mut bar = Bar::new(&tx);
+        bar.set_steps(tx_steps);
+
+        bar.load_config(config("[[bar]]\nwidget = \"time\"\n"));
+        assert_eq!(None, bar.watchers["time"].0);
+        assert_eq!(2, rx_steps.borrow().volume_up);
+
+        // A new interval restarts the watcher
+        bar.load_config(config(concat!(
+            "[steps]\n",
+            "volume_up = 10\n",
+            "[[bar]]\n",
+            "widget = \"time\"\n",
+            "interval = 0.5\n",
+        )));
+        assert_eq!(Some(Duration::from_millis(500)), bar.watchers["time"].0);
+        assert_eq!(10, rx_steps.borrow().volume_up);
+    }
+
+    #[tokio::test]
+    async fn split_nine_blocks() {
+        let config: Config = toml::from_str(concat!(
+            "[[bar]]\n",
+            "widget = \"time\"\n",
+            "[[bar]]\n",
+            "widget = \"nine\"\n",
+            "split = \"cells\"\n",
+            "rules = [{ when = \"current == true\", background = \"#0000ff\" }]\n",
+            "[nine]\n",
+            "rows = 2\n",
+            "cols = 2\n",
+            "cells = [1, { num = 2, label = \"web\" }, 3, 4]\n",
+        ))
+        .unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut bar = Bar::new(&tx);
+        bar.load_config(config);
+        bar.nine.go(NineCmd::MovedTo(Workspace {
+            num: Some(2),
+            name: "2:web".into(),
+            output: "eDP-1".into(),
+        }));
+        bar.update_nine();
+
+        let blocks: Vec<_> = bar.render_blocks().into_iter().skip(1).collect();
+        let texts: Vec<_> = blocks.iter().map(|(b, _)| b.full_text.as_deref()).collect();
+        assert_eq!(vec![Some("1"), Some("web"), Some("3"), Some("4")], texts);
+        let instances: Vec<_> = blocks.iter().map(|(b, _)| b.instance.as_deref()).collect();
+        assert_eq!(
+            vec![Some("1:0:0"), Some("1:0:1"), Some("1:1:0"), Some("1:1:1")],
+            instances
+        );
+        assert_eq!(Some("#0000ff"), blocks[1].0.background.as_deref());
+        assert_eq!(None, blocks[0].0.background);
+        assert_eq!(Some(false), blocks[0].0.separator);
+        assert_eq!(None, blocks[3].0.separator);
+
+        // Left clicks on a cell go there
+        let click = |instance: &str, button: &str| {
+            let json = format!(
+                r#"{{"name":"nine","instance":"{}","button":{},"event":272,"x":0,"y":0,"relative_x":0,"relative_y":0,"width":10,"height":10}}"#,
+                instance, button
+            );
+            serde_json::from_str::<Click>(&json).unwrap()
+        };
+        bar.handle_click(&click("1:1:0", "1"));
+        let position = at(1, 0);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Update::Nine(NineCmd::GoToOn(_, p))) if p == position
+        ));
+        assert!(matches!(rx.try_recv(), Ok(Update::Redraw)));
+        bar.handle_click(&click("1:1:0", "3"));
+        bar.handle_click(&click("0:1:0", "1"));
+        assert!(rx.try_recv().is_err());
+
+        // Or a block per column, on the current row
+        bar.layout().1[1].borrow_mut().split = Some(Split::Columns);
+        let texts: Vec<_> = bar
+            .render_blocks()
+            .into_iter()
+            .skip(1)
+            .map(|(b, _)| b.full_text.unwrap())
+            .collect();
+        assert_eq!(vec!["_", "T"], texts);
+    }
+
+    #[tokio::test]
+    async fn failed_exec_runs_again() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let bar = Bar::new(&tx);
+
+        bar.launch("1:web", "exit 3".into());
+        assert!(matches!(
+            timeout(Duration::from_secs(5), rx.recv()).await,
+            Ok(Some(Update::Nine(NineCmd::ExecFailed(name)))) if name == "1:web"
+        ));
+    }
+
+    #[test]
+    fn no_output_while_stopped() {
+        let mut bar = Bar::default();
+        let mut json = Vec::new();
+
+        bar.stopped = true;
+        bar.redraw(&mut json);
+        assert!(json.is_empty());
+
+        bar.stopped = false;
+        bar.redraw(&mut json);
+        assert_eq!("[],\n", String::from_utf8(json).unwrap());
+    }
+
+    fn noon_ish() -> i64 {
+        Local
+            .with_ymd_and_hms(2023, 6, 1, 12, 1, 0)
+            .unwrap()
+            .timestamp()
+    }
+
     #[tokio::test]
     async fn json_from_updates() {
         //let mut bar = Bar::new();
-        let config: Config = toml::from_str(concat!(
+        let mut bar = bar_from(concat!(
             "[default]\n",
             "[[bar]]\n",
             "widget = \"battery\"\n",
             "[[bar]]\n",
             "widget = \"time\"\n",
-        ))
-        .unwrap();
-        let mut bar = Bar {
-            battery_status: Some("Full".into()),
-            battery_capacity: Some("99".into()),
-            brightness: None,
-            window_name: None,
-            time: Some("12:01".into()),
-            volume: None,
-            mute: None,
-            config,
-            ..Default::default()
-        };
+            "format = \"%H:%M\"\n",
+        ));
 
         let (tx_updates, rx_updates) = mpsc::unbounded_channel::<Update>();
-        tx_updates.send(Update::Time(Some("12:01".into()))).unwrap();
         tx_updates
-            .send(Update::BatteryStatus(Some("Full".into())))
+            .send(Update::widget("time", [("timestamp", noon_ish().into())]))
+            .unwrap();
+        tx_updates
+            .send(Update::widget("battery", [("status", "Full".into())]))
             .unwrap();
         tx_updates
-            .send(Update::BatteryCapacity(Some("88".into())))
+            .send(Update::widget("battery", [("capacity", 88.into())]))
             .unwrap();
         tx_updates.send(Update::Redraw).unwrap();
         drop(tx_updates);
 
         let mut json = Vec::new();
-        bar.write_json(&mut json, rx_updates).await;
+        bar.write_bar(&mut json, rx_updates).await;
         let json = String::from_utf8(json).unwrap();
         // Remove trailing ",\n"
         let json = &json[0..json.len() - 2];
@@ -3,46 +3,81 @@ use std::time::Duration;
 
 use tokio::fs::read_to_string;
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::time::interval;
+use tokio::time::{interval, MissedTickBehavior};
 
 use crate::bar::Update;
 use crate::err::Res;
+use crate::signal::Paused;
+use crate::widget::{Value, Values, Watcher, Widget};
 
 const BAT0: &str = "/sys/class/power_supply/BAT0";
 
-#[derive(Debug)]
-pub struct Battery {
-    pub status: String,
-    pub capacity: String,
+#[derive(Debug, Default)]
+pub struct BatteryWidget(Values);
+
+impl Widget for BatteryWidget {
+    fn watch(
+        &self,
+        name: &str,
+        tx: UnboundedSender<Update>,
+        paused: Paused,
+        interval: Option<Duration>,
+    ) -> Option<Watcher> {
+        Some(Box::pin(watch_battery(
+            name.to_string(),
+            tx,
+            paused,
+            interval.unwrap_or(Duration::from_secs(5)),
+        )))
+    }
+
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        let mut values = self.0.clone();
+        if !values.contains_key("capacity") {
+            return Values::new();
+        }
+        let icon = match values.get("status").and_then(Value::as_str) {
+            Some("Full") | Some("Charging") => "🔌",
+            Some("Discharging") => "🔋",
+            _ => "n/a ",
+        };
+        values.insert("icon".into(), icon.into());
+        values
+    }
+
+    fn default_format(&self) -> &str {
+        "{capacity}{icon}"
+    }
 }
 
-pub async fn watch_battery(tx: UnboundedSender<Update>) -> Res<()> {
-    let mut idle = interval(Duration::from_secs(5));
+pub async fn watch_battery(
+    name: String,
+    tx: UnboundedSender<Update>,
+    mut paused: Paused,
+    period: Duration,
+) -> Res<()> {
+    let mut idle = interval(period);
+    idle.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     loop {
         idle.tick().await;
+        paused.wait().await;
 
-        let battery = Battery {
-            status: {
-                let path = Path::new(BAT0).join("status");
-                read_to_string(path)
-                    .await
-                    .unwrap()
-                    .trim()
-                    .to_string()
-            },
-            capacity: {
-                let path = Path::new(BAT0).join("capacity");
-                read_to_string(path)
-                    .await
-                    .unwrap()
-                    .trim()
-                    .to_string()
-            },
+        let status = read_to_string(Path::new(BAT0).join("status")).await?;
+        let capacity = read_to_string(Path::new(BAT0).join("capacity")).await?;
+        let capacity = match capacity.trim().parse::<i64>() {
+            Ok(capacity) => Value::Int(capacity),
+            Err(_) => capacity.trim().into(),
         };
 
-        tx.send(Update::BatteryStatus(Some(battery.status)))?;
-        tx.send(Update::BatteryCapacity(Some(battery.capacity)))?;
+        tx.send(Update::widget(
+            &name,
+            [("status", status.trim().into()), ("capacity", capacity)],
+        ))?;
         tx.send(Update::Redraw)?;
     }
 }
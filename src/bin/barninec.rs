@@ -5,7 +5,9 @@ use std::os::unix::net::UnixStream;
 use barnine::rpc::get_socket_path;
 
 fn main() -> std::io::Result<()> {
-    if let Some(cmd) = env::args().nth(1) {
+    // Commands may take arguments, like `profile docked`
+    let cmd = env::args().skip(1).collect::<Vec<_>>().join(" ");
+    if !cmd.is_empty() {
         let sock = get_socket_path("barnine");
         let mut stream = UnixStream::connect(sock)?;
         stream.write_all(cmd.as_bytes())?;
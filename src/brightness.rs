@@ -4,6 +4,29 @@ use tokio::fs::read_to_string;
 use zbus::azync::Connection as Dbus;
 
 use crate::err::{Error, Res};
+use crate::widget::{Values, Widget};
+
+/// Screen brightness, set through rpc commands.
+#[derive(Debug, Default)]
+pub struct BrightnessWidget(Values);
+
+impl Widget for BrightnessWidget {
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        let mut values = self.0.clone();
+        if !values.is_empty() {
+            values.insert("icon".into(), "🔅".into());
+        }
+        values
+    }
+
+    fn default_format(&self) -> &str {
+        "{percent:>2}{icon}"
+    }
+}
 
 #[derive(Debug)]
 pub enum Brightness {
@@ -17,14 +40,13 @@ pub enum Delta {
     DownPct(u32),
 }
 
-// TODO use clamp to limit range? or min/max
 pub async fn brighten(update: Brightness) -> Result<u32, Error> {
     match update {
         Brightness::Keyboard(delta) => {
             let (brt, brt_max) = cur_brt_with_max("leds", "smc::kbd_backlight").await?;
             let new_brt = match delta {
-                Delta::UpPct(amt) => brt + brt_max * amt / 100,
-                Delta::DownPct(amt) => brt - brt_max * amt / 100,
+                Delta::UpPct(amt) => (brt + brt_max * amt / 100).min(brt_max),
+                Delta::DownPct(amt) => brt.saturating_sub(brt_max * amt / 100),
             };
 
             let connection = Dbus::new_system().await?;
@@ -42,8 +64,8 @@ pub async fn brighten(update: Brightness) -> Result<u32, Error> {
         Brightness::Screen(delta) => {
             let (brt, brt_max) = cur_brt_with_max("backlight", "intel_backlight").await?;
             let new_brt = match delta {
-                Delta::UpPct(amt) => brt + brt_max * amt / 100,
-                Delta::DownPct(amt) => brt - brt_max * amt / 100,
+                Delta::UpPct(amt) => (brt + brt_max * amt / 100).min(brt_max),
+                Delta::DownPct(amt) => brt.saturating_sub(brt_max * amt / 100),
             };
 
             let connection = Dbus::new_system().await?;
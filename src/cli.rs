@@ -0,0 +1,169 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use futures::stream::StreamExt;
+use swaybar_types::{Header, Version};
+use tokio::spawn;
+use tokio::sync::{mpsc::unbounded_channel, watch};
+
+use crate::{
+    bar::Bar,
+    click::watch_clicks,
+    config::{self, get_config_file, read_config, watch_config, write_default_config},
+    logging::{get_cache_dir, init_logging},
+    output::Output,
+    rpc::watch_rpc,
+    schema::json_schema,
+    signal::watch_signals,
+    sway::watch_sway,
+    widget::Registry,
+};
+
+const USAGE: &str = concat!(
+    "usage: barnine [--config <path>] ",
+    "[--output swaybar|i3bar|text|ansi|lemonbar|waybar] ",
+    "[--bar-output <sway output>]\n",
+    "       barnine check-config [--config <path>] [--schema]"
+);
+
+#[derive(Debug, Default)]
+struct Args {
+    config: Option<PathBuf>,
+    output: Option<Output>,
+    bar_output: Option<String>,
+    check_config: bool,
+    schema: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut argv = env::args().skip(1);
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--config" | "-c" => {
+                let config = argv.next().ok_or("missing value for --config")?;
+                args.config = Some(PathBuf::from(config));
+            }
+            "--output" | "-o" => {
+                let output = argv.next().ok_or("missing value for --output")?;
+                args.output = Some(output.parse()?);
+            }
+            "--bar-output" => {
+                let output = argv.next().ok_or("missing value for --bar-output")?;
+                args.bar_output = Some(output);
+            }
+            "check-config" => args.check_config = true,
+            "--schema" => args.schema = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    if args.schema && !args.check_config {
+        return Err("--schema only works with check-config".into());
+    }
+
+    Ok(args)
+}
+
+/// Report config errors without starting the bar, or print the config schema.
+fn check_config(config_file: &Path, schema: bool, registry: &Registry) -> i32 {
+    if schema {
+        println!("{:#}", json_schema(registry));
+        return 0;
+    }
+
+    let errors = config::check_config(config_file, registry);
+    for err in &errors {
+        eprintln!("{}", err);
+    }
+    if errors.is_empty() {
+        println!("{}: ok", config_file.display());
+        0
+    } else {
+        1
+    }
+}
+
+/// Run barnine from its command line arguments, with the widgets of
+/// `registry`: check the config, or write the bar to stdout for good.
+///
+/// A binary with its own widgets can call this with `Registry::default()`
+/// plus those widgets, instead of copying barnine's `main`.
+pub async fn run(registry: Registry) {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        exit(2);
+    });
+    let config_file = match args.config {
+        Some(path) => path.into_boxed_path(),
+        None => get_config_file("barnine").unwrap(),
+    };
+    if args.check_config {
+        exit(check_config(&config_file, args.schema, &registry));
+    }
+    let _guard = init_logging("barnine");
+    if let Err(err) = write_default_config(&config_file) {
+        tracing::error!("Could not write default config: {:?}", err);
+    }
+    let config = read_config(&config_file);
+    let output = args.output.or(config.output).unwrap_or_default();
+
+    // Catch the hide and show signals before telling swaybar about them
+    let (tx_updates, rx_updates) = unbounded_channel();
+    let signal_watcher = watch_signals(tx_updates.clone(), &config.signals).unwrap_or_else(|err| {
+        tracing::error!("Could not catch signals: {:?}", err);
+        None
+    });
+    let signals = signal_watcher.as_ref().and(config.signals.pair());
+
+    // `man swaybar-protocol`
+    let header = Header {
+        version: Version::One,
+        stop_signal: signals.map(|(stop, _)| stop.0 as u32),
+        cont_signal: signals.map(|(_, cont)| cont.0 as u32),
+        click_events: Some(output.click_events()),
+    };
+    output
+        .write_header(&mut std::io::stdout(), &header)
+        .unwrap();
+
+    // Spawn stats collecting workers, with widget watchers started by the bar
+    let mut bar = Bar::new(&tx_updates);
+    bar.set_registry(registry);
+    bar.set_output(output);
+    bar.set_bar_output(args.bar_output);
+    bar.set_state_file(get_cache_dir("barnine").join("nine.json"));
+    let (tx_steps, rx_steps) = watch::channel(config.steps.clone());
+    bar.set_steps(tx_steps);
+    let mut workers = vec![
+        spawn(watch_rpc(tx_updates.clone(), rx_steps)),
+        spawn(watch_sway(tx_updates.clone())),
+        spawn(watch_config(tx_updates.clone(), config_file)),
+    ];
+    if let Some(signal_watcher) = signal_watcher {
+        workers.push(spawn(signal_watcher));
+    }
+    if output.click_events() {
+        workers.push(spawn(watch_clicks(tx_updates.clone())));
+    }
+    let futures_stream = futures::stream::iter(workers);
+
+    // Log worker failures
+    let mut worker_errors = futures_stream.buffer_unordered(5);
+    tokio::spawn(async move {
+        while let Some(error) = worker_errors.next().await {
+            tracing::error!("{:?}", error);
+        }
+    });
+
+    // Write the bar
+    bar.write_bar(&mut std::io::stdout(), rx_updates).await;
+
+    unreachable!()
+}
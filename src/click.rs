@@ -0,0 +1,127 @@
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use swaybar_types::{Button, Click};
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, trace};
+
+use crate::{bar::Update, err::Res};
+
+/// Shell commands to run when a block is clicked, configured per `[[bar]]`
+/// entry as e.g. `on_click = { left = "pavucontrol", wheel_up = "barninec volume_up" }`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClickActions {
+    left: Option<String>,
+    middle: Option<String>,
+    right: Option<String>,
+    wheel_up: Option<String>,
+    wheel_down: Option<String>,
+    wheel_left: Option<String>,
+    wheel_right: Option<String>,
+}
+
+impl ClickActions {
+    pub fn get(&self, button: Button) -> Option<&String> {
+        match button {
+            Button::Left => self.left.as_ref(),
+            Button::Middle => self.middle.as_ref(),
+            Button::Right => self.right.as_ref(),
+            Button::WheelUp => self.wheel_up.as_ref(),
+            Button::WheelDown => self.wheel_down.as_ref(),
+            Button::WheelLeft => self.wheel_left.as_ref(),
+            Button::WheelRight => self.wheel_right.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Read the infinite json-array of click events that swaybar writes to stdin.
+pub async fn watch_clicks(tx: UnboundedSender<Update>) -> Res<()> {
+    trace!("Start watch_clicks");
+    let mut lines = BufReader::new(io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if let Some(click) = parse_click(&line) {
+            tx.send(Update::Click(click))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse one line of the click event stream, which is either the opening `[`
+/// or a json object optionally preceded by a comma.
+fn parse_click(line: &str) -> Option<Click> {
+    let line = line.trim().trim_start_matches(['[', ',']).trim();
+    if line.is_empty() {
+        return None;
+    }
+    match serde_json::from_str(line) {
+        Ok(click) => Some(click),
+        Err(err) => {
+            debug!("Bad click event {:?}: {:?}", line, err);
+            None
+        }
+    }
+}
+
+/// Run a click action in the background, keeping it off of the bar's stdio.
+pub fn run_action(action: &str) {
+    debug!("Running click action: {}", action);
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(action)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            tokio::spawn(async move { child.wait().await });
+        }
+        Err(err) => error!("Could not run {:?}: {:?}", action, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swaybar_types::Button;
+
+    use super::{parse_click, ClickActions};
+
+    const CLICK: &str = concat!(
+        "{\"name\":\"volume\",\"instance\":\"3\",\"x\":1800,\"y\":10,\"button\":4,",
+        "\"event\":4,\"relative_x\":12,\"relative_y\":10,\"width\":40,\"height\":20}"
+    );
+
+    #[test]
+    fn parse_click_stream() {
+        assert!(parse_click("[").is_none());
+        assert!(parse_click("").is_none());
+
+        let first = parse_click(CLICK).unwrap();
+        assert_eq!(Some("volume".into()), first.name);
+        assert_eq!(Some("3".into()), first.instance);
+        assert_eq!(Button::WheelUp, first.button);
+
+        let next = parse_click(&format!(",{}", CLICK)).unwrap();
+        assert_eq!(Button::WheelUp, next.button);
+
+        assert!(parse_click(",{\"garbage\":true}").is_none());
+    }
+
+    #[test]
+    fn click_actions_from_toml() {
+        let actions: ClickActions =
+            toml::from_str("left = \"pavucontrol\"\nwheel_up = \"barninec volume_up\"").unwrap();
+        assert_eq!(Some(&"pavucontrol".into()), actions.get(Button::Left));
+        assert_eq!(
+            Some(&"barninec volume_up".into()),
+            actions.get(Button::WheelUp)
+        );
+        assert!(actions.get(Button::Right).is_none());
+    }
+}
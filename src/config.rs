@@ -1,103 +1,455 @@
 use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use notify::event::DataChange::Any;
-use notify::event::ModifyKind::Data;
-use notify::EventKind::Modify;
 use notify::RecursiveMode::NonRecursive;
-use notify::{Event, RecommendedWatcher, Watcher};
+use notify::{Event, EventKind, RecommendedWatcher, Watcher};
 use serde_derive::Deserialize;
-use tokio::fs;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tracing::debug;
+use tokio::time::{timeout_at, Duration, Instant};
+use toml::{Table, Value};
+use tracing::{debug, error, info};
 
 use crate::bar::{Block, Update};
 use crate::err::Res;
+use crate::nine::{Grid, Minimap};
+use crate::output::{rgb, Output};
+use crate::rpc::Steps;
+use crate::signal::Signals;
+use crate::widget::Registry;
 
+/// Settings from the config file, merged with any files it includes and the
+/// drop-ins next to it, so each part may leave out `default` or `bar`.
 #[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+    pub include: Vec<PathBuf>,
     pub default: RefCell<Block>,
     pub bar: Vec<RefCell<Block>>,
+    /// Profile shown at startup instead of the top-level `default` and `bar`
+    pub profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+    pub signals: Signals,
+    pub steps: Steps,
+    pub nine: Grid,
+    pub minimap: Minimap,
+    pub output: Option<Output>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            include: Vec::new(),
             default: RefCell::new(Block::default()),
             bar: Vec::new(),
+            profile: None,
+            profiles: BTreeMap::new(),
+            signals: Signals::default(),
+            steps: Steps::default(),
+            nine: Grid::default(),
+            minimap: Minimap::default(),
+            output: None,
         }
     }
 }
 
-pub async fn watch_config(tx_updates: UnboundedSender<Update>) -> Res<()> {
-    // TODO create config dir and sample barnine.toml if absent
-    debug!("in watch_config thread");
-    let (tx_watcher, mut rx_watcher) = unbounded_channel::<()>();
-    let mut config: RecommendedWatcher = Watcher::new(
-        move |e|
-
-    //match e {
-    if let Ok(event) = e {
-        if let Event {
-            kind: Modify(Data(Any)),
-            paths: ref p,
-            ..
-        } = event
+/// A named bar layout, configured as `[profiles.<name>]` with its own
+/// `default` block and `[[profiles.<name>.bar]]` entries, and switched to
+/// with the `profile <name>` command.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub default: RefCell<Block>,
+    pub bar: Vec<RefCell<Block>>,
+}
+
+impl Config {
+    /// The `default` block and `bar` entries of a profile, falling back to
+    /// the startup profile and then the top-level layout.
+    pub fn layout(&self, profile: Option<&str>) -> (&RefCell<Block>, &[RefCell<Block>]) {
+        match profile
+            .or(self.profile.as_deref())
+            .and_then(|name| self.profiles.get(name))
         {
-            // Confirm that the modification is on the watched file
-            let watched_file = get_config_file("barnine").unwrap();
-            if p.contains(&std::path::PathBuf::from(watched_file)) {
-                debug!("got event-->>{:?}", event);
-                tx_watcher.send(()).unwrap();
+            Some(profile) => (&profile.default, &profile.bar),
+            None => (&self.default, &self.bar),
+        }
+    }
+
+    /// Check what deserializing can't: widget names, colors, profiles, the
+    /// grid size and step sizes.
+    pub fn validate(&self, registry: &Registry) -> Vec<String> {
+        let mut errors = check_layout("", &self.default, &self.bar, registry);
+        errors.extend(self.nine.validate());
+        errors.extend(self.signals.validate());
+        errors.extend(self.steps.validate());
+
+        for (name, profile) in &self.profiles {
+            let prefix = format!("profiles.{}.", name);
+            errors.extend(check_layout(
+                &prefix,
+                &profile.default,
+                &profile.bar,
+                registry,
+            ));
+        }
+        if let Some(ref profile) = self.profile {
+            if !self.profiles.contains_key(profile) {
+                errors.push(format!("profile: unknown profile `{}`", profile));
             }
         }
-    }, //_ => {}
-    )
-    .unwrap();
-
-    debug!(
-        "about to watch config file:{:?}",
-        get_config_file("barnine").unwrap()
-    );
-
-    config
-        .watch(
-            // TODO why do I need to monitor parent and not the file..
-            //      with just the file: no Modify(Data(Any)) received.. ?!?
-            get_config_file("barnine").unwrap().parent().unwrap(),
-            NonRecursive,
-        )
-        .unwrap();
 
-    // Load config file at startup
-    send_config_update("barnine", tx_updates.clone()).await?;
+        errors
+    }
+}
+
+fn check_layout(
+    prefix: &str,
+    default: &RefCell<Block>,
+    bar: &[RefCell<Block>],
+    registry: &Registry,
+) -> Vec<String> {
+    let mut errors = check_colors(&format!("{}default", prefix), &default.borrow());
+    errors.extend(check_default(
+        &format!("{}default", prefix),
+        &default.borrow(),
+    ));
 
-    while let Some(()) = rx_watcher.recv().await {
-        debug!("got Modify(Data()) recv");
-        send_config_update("barnine", tx_updates.clone()).await?;
+    for (i, block) in bar.iter().enumerate() {
+        let block = block.borrow();
+        let name = format!("{}bar[{}]", prefix, i);
+        match block.widget {
+            Some(ref widget) if !registry.contains(widget) => {
+                errors.push(format!("{}: unknown widget `{}`", name, widget));
+            }
+            Some(_) => {}
+            None => errors.push(format!("{}: missing `widget`", name)),
+        }
+        errors.extend(check_colors(&name, &block));
+        if block.split.is_some() && block.widget.as_deref() != Some("nine") {
+            errors.push(format!("{}: `split` only works for the nine widget", name));
+        }
+        if block.interval.is_some() && block.interval().is_none() {
+            errors.push(format!(
+                "{}: `interval` should be a positive number of seconds",
+                name
+            ));
+        }
     }
 
+    errors
+}
+
+/// Keys that only mean something on a block of a widget, and that the
+/// default block would otherwise ignore.
+fn check_default(name: &str, block: &Block) -> Vec<String> {
+    let keys = [
+        ("widget", block.widget.is_some()),
+        ("format", block.format.is_some()),
+        ("rules", block.rules.is_some()),
+        ("on_click", block.on_click.is_some()),
+        ("interval", block.interval.is_some()),
+        ("split", block.split.is_some()),
+    ];
+
+    keys.iter()
+        .filter(|(_, set)| *set)
+        .map(|(key, _)| format!("{}: `{}` only works in `bar` blocks", name, key))
+        .collect()
+}
+
+fn check_colors(name: &str, block: &Block) -> Vec<String> {
+    let mut colors = vec![
+        ("color", &block.color),
+        ("background", &block.background),
+        ("border", &block.border),
+    ];
+    for rule in block.rules.iter().flatten() {
+        colors.push(("color", &rule.color));
+        colors.push(("background", &rule.background));
+        colors.push(("border", &rule.border));
+    }
+
+    colors
+        .into_iter()
+        .filter_map(|(key, color)| match color {
+            Some(color) if rgb(color).is_none() => Some(format!(
+                "{}: bad {} `{}`, expected #rrggbb or #rrggbbaa",
+                name, key, color
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse a config file, with the position of any syntax error in the message.
+pub fn parse_config(path: &Path, toml: &str) -> Result<Config, String> {
+    toml::from_str(toml).map_err(|err| match err.span() {
+        Some(span) => {
+            let before = &toml[..span.start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+            format!(
+                "{}:{}:{}: {}",
+                path.display(),
+                line,
+                column,
+                err.message().trim()
+            )
+        }
+        None => format!("{}: {}", path.display(), err.message().trim()),
+    })
+}
+
+/// Documented starter config, written on first run.
+pub const DEFAULT_CONFIG: &str = include_str!("../examples/barnine.toml");
+
+/// Create the config dir and a starter config file if the file is absent.
+pub fn write_default_config(config_file: &Path) -> Res<bool> {
+    if config_file.exists() {
+        return Ok(false);
+    }
+    if let Some(config_dir) = config_file.parent() {
+        std::fs::create_dir_all(config_dir)?;
+    }
+    std::fs::write(config_file, DEFAULT_CONFIG)?;
+    info!("Wrote default config to {:?}", config_file);
+
+    Ok(true)
+}
+
+/// Load the config file synchronously, for settings needed before the bar starts.
+pub fn read_config(config_file: &Path) -> Config {
+    load_config(config_file).0.unwrap_or_default()
+}
+
+/// Every problem that would keep the config from loading, or leave blocks
+/// that the bar can't show, for checking a config before the bar reloads it.
+pub fn check_config(config_file: &Path, registry: &Registry) -> Vec<String> {
+    match load_config(config_file).0 {
+        Ok(config) => config.validate(registry),
+        Err(err) => vec![err],
+    }
+}
+
+/// Directory of config fragments next to the config file, e.g. `barnine.d`.
+fn drop_in_dir(config_file: &Path) -> PathBuf {
+    config_file.with_extension("d")
+}
+
+/// Read the config file with its includes and drop-ins, and list every file
+/// that was read so they can all be watched.
+///
+/// Included files come before the file that includes them, followed by the
+/// `*.toml` drop-ins sorted by name.  Later files override the fields of
+/// earlier ones, except for `bar` entries, which are appended.
+pub fn load_config(config_file: &Path) -> (Result<Config, String>, Vec<PathBuf>) {
+    let mut sources = Vec::new();
+    let mut merged = Table::new();
+    let mut result = read_layers(config_file, &mut merged, &mut sources);
+
+    let mut drop_ins: Vec<PathBuf> = std::fs::read_dir(drop_in_dir(config_file))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension() == Some("toml".as_ref()))
+        .collect();
+    drop_ins.sort();
+    for drop_in in drop_ins {
+        if result.is_ok() {
+            result = read_layers(&drop_in, &mut merged, &mut sources);
+        }
+    }
+
+    let config = result.and_then(|()| {
+        Value::Table(merged)
+            .try_into()
+            .map_err(|err: toml::de::Error| {
+                format!("{}: {}", config_file.display(), err.message().trim())
+            })
+    });
+    (config, sources)
+}
+
+/// Merge a config file and the files it includes onto `merged`.
+fn read_layers(path: &Path, merged: &mut Table, sources: &mut Vec<PathBuf>) -> Result<(), String> {
+    // Files included twice, or in a loop, are only read the first time.
+    // Sources keep the path as written, so that symlinks are watched too.
+    let canonical = path
+        .canonicalize()
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    if sources
+        .iter()
+        .any(|source| source.canonicalize().ok().as_ref() == Some(&canonical))
+    {
+        return Ok(());
+    }
+    sources.push(path.to_path_buf());
+
+    let toml =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let config = parse_config(path, &toml)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    for include in config.include {
+        read_layers(&dir.join(include), merged, sources)?;
+    }
+
+    let mut layer: Table = toml::from_str(&toml).map_err(|err| err.to_string())?;
+    layer.remove("include");
+    merge(merged, layer);
+
     Ok(())
 }
 
-async fn send_config_update(app_name: &str, tx_updates: UnboundedSender<Update>) -> Res<()> {
-    let config_file = get_config_file(app_name).unwrap();
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (Some(Value::Array(base)), Value::Array(layer)) if key == "bar" => base.extend(layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+pub async fn watch_config(tx_updates: UnboundedSender<Update>, config_file: Box<Path>) -> Res<()> {
+    debug!("in watch_config thread");
+    let (tx_watcher, mut rx_watcher) = unbounded_channel::<Event>();
+    let mut watcher: RecommendedWatcher = Watcher::new(move |event| {
+        if let Ok(event) = event {
+            let _ = tx_watcher.send(event);
+        }
+    })?;
 
-    if config_file.is_file() {
-        let toml: String = fs::read_to_string(&config_file).await.unwrap();
-        let config: Result<Config, _> = toml::from_str(&toml);
+    // Watch directories rather than files, so that editors which save by
+    // renaming a temp file over the config, and relinked dotfiles, are seen
+    let mut watched_dirs = HashSet::new();
 
-        tx_updates
-            .send(Update::Config(Box::new(config.unwrap())))
-            .unwrap();
-        tx_updates.send(Update::Redraw).unwrap();
+    // Load config file at startup
+    let sources = send_config_update(&config_file, tx_updates.clone()).await?;
+    let mut config_paths = watch_config_paths(&mut watcher, &mut watched_dirs, &sources);
+    let drop_in_dir = drop_in_dir(&config_file);
+
+    while let Some(event) = rx_watcher.recv().await {
+        if !is_config_event(&event, &config_paths, &drop_in_dir) {
+            continue;
+        }
+        debug!("got config event-->>{:?}", event);
+
+        // Wait out the burst of events from a single save, which only
+        // events on the config files themselves extend
+        let mut deadline = Instant::now() + DEBOUNCE;
+        loop {
+            match timeout_at(deadline, rx_watcher.recv()).await {
+                Ok(Some(event)) => {
+                    if is_config_event(&event, &config_paths, &drop_in_dir) {
+                        deadline = Instant::now() + DEBOUNCE;
+                    }
+                }
+                Ok(None) => return Ok(()),
+                Err(_) => break,
+            }
+        }
+
+        let sources = send_config_update(&config_file, tx_updates.clone()).await?;
+        config_paths = watch_config_paths(&mut watcher, &mut watched_dirs, &sources);
     }
 
     Ok(())
 }
 
-fn get_config_file(app_name: &str) -> Res<Box<Path>> {
+/// Time to wait for more file events before reloading.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Paths of the config files and their symlink targets, with their
+/// directories added to the watcher.  The first source is the config file,
+/// whose drop-in directory is watched too.
+fn watch_config_paths(
+    watcher: &mut RecommendedWatcher,
+    watched_dirs: &mut HashSet<PathBuf>,
+    sources: &[PathBuf],
+) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for source in sources {
+        paths.push(source.clone());
+        if let Ok(target) = std::fs::canonicalize(source) {
+            if &target != source {
+                paths.push(target);
+            }
+        }
+    }
+
+    let mut dirs: Vec<PathBuf> = paths
+        .iter()
+        .filter_map(|path| path.parent())
+        .map(Path::to_path_buf)
+        .collect();
+    if let Some(config_file) = sources.first() {
+        let drop_in_dir = drop_in_dir(config_file);
+        paths.push(drop_in_dir.clone());
+        if drop_in_dir.is_dir() {
+            dirs.push(drop_in_dir);
+        }
+    }
+
+    for dir in dirs {
+        if !watched_dirs.contains(&dir) {
+            debug!("about to watch config dir:{:?}", dir);
+            match watcher.watch(&dir, NonRecursive) {
+                Ok(()) => {
+                    watched_dirs.insert(dir);
+                }
+                Err(err) => error!("Could not watch {:?}: {:?}", dir, err),
+            }
+        }
+    }
+
+    paths
+}
+
+/// Whether an event touches a config file, or a fragment in the drop-in
+/// directory.
+fn is_config_event(event: &Event, config_paths: &[PathBuf], drop_in_dir: &Path) -> bool {
+    let is_drop_in = |path: &PathBuf| {
+        path.extension() == Some("toml".as_ref()) && path.parent() == Some(drop_in_dir)
+    };
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) && event
+        .paths
+        .iter()
+        .any(|path| config_paths.contains(path) || is_drop_in(path))
+}
+
+/// Send the config, or why it couldn't be loaded, and return the files it was
+/// read from.
+async fn send_config_update(
+    config_file: &Path,
+    tx_updates: UnboundedSender<Update>,
+) -> Res<Vec<PathBuf>> {
+    if !config_file.is_file() {
+        return Ok(vec![config_file.to_path_buf()]);
+    }
+
+    // Keep the last good config until the files are fixed
+    let (config, sources) = load_config(config_file);
+    match config {
+        Ok(config) => tx_updates.send(Update::Config(Box::new(config)))?,
+        Err(err) => {
+            error!("Bad config: {}", err);
+            tx_updates.send(Update::ConfigError(err))?;
+        }
+    }
+    tx_updates.send(Update::Redraw)?;
+
+    Ok(sources)
+}
+
+pub fn get_config_file(app_name: &str) -> Res<Box<Path>> {
     // Look for APPNAME_DEV_DIR environment variable to override default
     let mut dev_dir = app_name.to_uppercase();
 
@@ -121,3 +473,285 @@ fn get_config_file(app_name: &str) -> Res<Box<Path>> {
 
     Ok(config_path.into_boxed_path())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+    use tokio::sync::mpsc;
+    use tokio::time::{timeout, Duration};
+
+    use super::{
+        check_config, load_config, parse_config, read_config, watch_config, write_default_config,
+        DEFAULT_CONFIG,
+    };
+    use crate::bar::Update;
+    use crate::widget::Registry;
+
+    async fn next_config(rx: &mut mpsc::UnboundedReceiver<Update>) -> usize {
+        loop {
+            let update = timeout(Duration::from_secs(5), rx.recv()).await;
+            if let Update::Config(config) = update.unwrap().unwrap() {
+                return config.bar.len();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_on_atomic_save_and_symlink() {
+        let dir = tempdir().unwrap();
+        let dotfiles = dir.path().join("dotfiles");
+        let config_dir = dir.path().join("config");
+        std::fs::create_dir_all(&dotfiles).unwrap();
+        std::fs::create_dir_all(&config_dir).unwrap();
+
+        // Config file is a symlink into a dotfiles repo
+        let target = dotfiles.join("barnine.toml");
+        let config_file = config_dir.join("barnine.toml");
+        std::fs::write(&target, "bar = []\n[default]\n").unwrap();
+        std::os::unix::fs::symlink(&target, &config_file).unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(watch_config(tx, config_file.into_boxed_path()));
+        assert_eq!(0, next_config(&mut rx).await);
+
+        // Editor writes a temp file and renames it over the target
+        let temp = dotfiles.join(".barnine.toml.swp");
+        std::fs::write(&temp, "[default]\n[[bar]]\nwidget = \"time\"\n").unwrap();
+        std::fs::rename(&temp, &target).unwrap();
+        assert_eq!(1, next_config(&mut rx).await);
+
+        // A burst of writes reloads once
+        for _ in 0..5 {
+            std::fs::write(
+                &target,
+                "[default]\n[[bar]]\nwidget = \"nine\"\n[[bar]]\nwidget = \"time\"\n",
+            )
+            .unwrap();
+        }
+        assert_eq!(2, next_config(&mut rx).await);
+        let more = timeout(Duration::from_millis(300), next_config(&mut rx)).await;
+        assert!(more.is_err());
+    }
+
+    #[tokio::test]
+    async fn reload_on_include_and_drop_in() {
+        let dir = tempdir().unwrap();
+        let theme = dir.path().join("theme.toml");
+        let config_file = dir.path().join("barnine.toml");
+        std::fs::write(&theme, "[default]\ncolor = \"#ffffff\"\n").unwrap();
+        std::fs::write(&config_file, "include = [\"theme.toml\"]\n").unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(watch_config(tx, config_file.into_boxed_path()));
+        assert_eq!(0, next_config(&mut rx).await);
+
+        // Included files are watched
+        std::fs::write(&theme, "[[bar]]\nwidget = \"time\"\n").unwrap();
+        assert_eq!(1, next_config(&mut rx).await);
+
+        // So is the drop-in directory, even when created later
+        let drop_ins = dir.path().join("barnine.d");
+        std::fs::create_dir(&drop_ins).unwrap();
+        assert_eq!(1, next_config(&mut rx).await);
+        std::fs::write(
+            drop_ins.join("laptop.toml"),
+            "[[bar]]\nwidget = \"battery\"\n",
+        )
+        .unwrap();
+        assert_eq!(2, next_config(&mut rx).await);
+    }
+
+    #[test]
+    fn merge_includes_and_drop_ins() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("barnine.toml");
+        let drop_ins = dir.path().join("barnine.d");
+        std::fs::create_dir_all(dir.path().join("themes")).unwrap();
+        std::fs::create_dir_all(&drop_ins).unwrap();
+
+        std::fs::write(
+            dir.path().join("themes").join("dark.toml"),
+            "[default]\nbackground = \"#000000\"\ncolor = \"#ffffff\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &config_file,
+            concat!(
+                "include = [\"themes/dark.toml\"]\n",
+                "[default]\n",
+                "color = \"#eeeeee\"\n",
+                "[[bar]]\n",
+                "widget = \"time\"\n",
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            drop_ins.join("20-battery.toml"),
+            "[[bar]]\nwidget = \"battery\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            drop_ins.join("10-nine.toml"),
+            "[[bar]]\nwidget = \"nine\"\n",
+        )
+        .unwrap();
+        std::fs::write(drop_ins.join("notes.txt"), "not config").unwrap();
+
+        let (config, sources) = load_config(&config_file);
+        let config = config.unwrap();
+        assert_eq!(4, sources.len());
+
+        // The including file overrides the theme
+        let default = config.default.borrow();
+        assert_eq!(Some("#000000"), default.background.as_deref());
+        assert_eq!(Some("#eeeeee"), default.color.as_deref());
+
+        // Drop-ins append their blocks in name order
+        let widgets: Vec<_> = config
+            .bar
+            .iter()
+            .map(|block| block.borrow().widget.clone().unwrap())
+            .collect();
+        assert_eq!(vec!["time", "nine", "battery"], widgets);
+
+        // Errors point at the file they are in
+        std::fs::write(drop_ins.join("10-nine.toml"), "[[bar]]\nwidgt = \"nine\"\n").unwrap();
+        let err = load_config(&config_file).0.unwrap_err();
+        assert!(err.contains("10-nine.toml:2:1: "), "{}", err);
+    }
+
+    #[test]
+    fn include_each_file_once() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("barnine.toml");
+        std::fs::create_dir_all(dir.path().join("themes")).unwrap();
+
+        // A theme that includes the file including it, spelled through `..`
+        std::fs::write(
+            dir.path().join("themes").join("dark.toml"),
+            "include = [\"../barnine.toml\"]\n[[bar]]\nwidget = \"battery\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &config_file,
+            concat!(
+                "include = [\"themes/dark.toml\", \"./themes/dark.toml\", \"themes/../themes/dark.toml\"]\n",
+                "[[bar]]\n",
+                "widget = \"time\"\n",
+            ),
+        )
+        .unwrap();
+
+        let (config, sources) = load_config(&config_file);
+        let config = config.unwrap();
+        assert_eq!(
+            vec![config_file, dir.path().join("themes/dark.toml")],
+            sources
+        );
+
+        // Blocks from a file included twice are only appended once
+        let widgets: Vec<_> = config
+            .bar
+            .iter()
+            .map(|block| block.borrow().widget.clone().unwrap())
+            .collect();
+        assert_eq!(vec!["battery", "time"], widgets);
+    }
+
+    #[test]
+    fn syntax_error_position() {
+        let path = Path::new("barnine.toml");
+        let toml = "[default]\n[[bar]]\nwidget = \"time\"\ncolr = \"#ffffff\"\n";
+        let err = parse_config(path, toml).unwrap_err();
+
+        assert!(err.starts_with("barnine.toml:4:1: "), "{}", err);
+        assert!(err.contains("colr"));
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let path = Path::new("examples/barnine.toml");
+        let config = parse_config(path, include_str!("../examples/barnine.toml")).unwrap();
+        assert!(config.validate(&Registry::default()).is_empty());
+    }
+
+    #[test]
+    fn default_config_on_first_run() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("barnine").join("barnine.toml");
+
+        assert!(write_default_config(&config_file).unwrap());
+        assert!(!read_config(&config_file).bar.is_empty());
+
+        // Never overwrite an existing config
+        std::fs::write(&config_file, "bar = []\n[default]\n").unwrap();
+        assert!(!write_default_config(&config_file).unwrap());
+        assert!(read_config(&config_file).bar.is_empty());
+    }
+
+    #[test]
+    fn check_config_reports_errors() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("barnine.toml");
+        let registry = Registry::default();
+
+        std::fs::write(&config_file, "[[bar]]\nwidget = \"time\"\nurgent = 1\n").unwrap();
+        let errors = check_config(&config_file, &registry);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("barnine.toml:3:10: "), "{}", errors[0]);
+
+        std::fs::write(&config_file, "[[bar]]\nwidget = \"tim\"\n").unwrap();
+        let errors = check_config(&config_file, &registry);
+        assert_eq!(vec!["bar[0]: unknown widget `tim`"], errors);
+
+        std::fs::write(&config_file, DEFAULT_CONFIG).unwrap();
+        assert!(check_config(&config_file, &registry).is_empty());
+    }
+
+    #[test]
+    fn validate_widgets_and_colors() {
+        let path = Path::new("barnine.toml");
+        let toml = concat!(
+            "[default]\n",
+            "background = \"blue\"\n",
+            "[[bar]]\n",
+            "widget = \"batery\"\n",
+            "[[bar]]\n",
+            "widget = \"battery\"\n",
+            "rules = [{ when = \"capacity < 15\", color = \"#ff00\" }]\n",
+            "interval = 0\n",
+            "split = \"cells\"\n",
+        );
+        let config = parse_config(path, toml).unwrap();
+        let errors = config.validate(&Registry::default());
+
+        assert_eq!(5, errors.len());
+        assert!(errors[0].starts_with("default: bad background `blue`"));
+        assert_eq!("bar[0]: unknown widget `batery`", errors[1]);
+        assert!(errors[2].starts_with("bar[1]: bad color `#ff00`"));
+        assert_eq!("bar[1]: `split` only works for the nine widget", errors[3]);
+        assert!(errors[4].starts_with("bar[1]: `interval` should be"));
+
+        let toml = concat!(
+            "profile = \"docked\"\n",
+            "[profiles.talk.default]\n",
+            "format = \"{name}\"\n",
+            "interval = 2\n",
+            "[[profiles.talk.bar]]\n",
+            "widget = \"tim\"\n",
+        );
+        let config = parse_config(path, toml).unwrap();
+        let errors = config.validate(&Registry::default());
+        assert_eq!(
+            vec![
+                "profiles.talk.default: `format` only works in `bar` blocks",
+                "profiles.talk.default: `interval` only works in `bar` blocks",
+                "profiles.talk.bar[0]: unknown widget `tim`",
+                "profile: unknown profile `docked`",
+            ],
+            errors
+        );
+    }
+}
@@ -11,6 +11,8 @@ pub enum Error {
     DbusMessageError(zbus::MessageError),
     FmtError(std::fmt::Error),
     IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    NotifyError(notify::Error),
     StdNumError(std::num::ParseIntError),
     SwayipcError(swayipc_async::Error),
     TokioError(SendError<Update>),
@@ -46,6 +48,18 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::JsonError(error)
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(error: notify::Error) -> Self {
+        Error::NotifyError(error)
+    }
+}
+
 impl From<std::fmt::Error> for Error {
     fn from(error: std::fmt::Error) -> Self {
         Error::FmtError(error)
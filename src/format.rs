@@ -0,0 +1,75 @@
+use crate::widget::Values;
+
+/// Fill in a `format` template from widget values.
+///
+/// Placeholders are written as `{name}`, optionally with an alignment and
+/// width as in `{percent:>3}`.  Use `{{` and `}}` for literal braces.  Names
+/// that the widget doesn't have render as empty strings.
+pub fn render(template: &str, values: &Values) -> String {
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    placeholder.push(c);
+                }
+                result.push_str(&render_placeholder(&placeholder, values));
+            }
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn render_placeholder(placeholder: &str, values: &Values) -> String {
+    let (name, spec) = match placeholder.split_once(':') {
+        Some((name, spec)) => (name.trim(), spec),
+        None => (placeholder.trim(), ""),
+    };
+    let value = values.get(name).map(|v| v.to_string()).unwrap_or_default();
+
+    let (align, width) = match spec.chars().next() {
+        Some(c @ '<') | Some(c @ '>') | Some(c @ '^') => (c, &spec[1..]),
+        _ => ('<', spec),
+    };
+    let width = width.parse::<usize>().unwrap_or(0);
+
+    match align {
+        '>' => format!("{:>1$}", value, width),
+        '^' => format!("{:^1$}", value, width),
+        _ => format!("{:<1$}", value, width),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::widget::values;
+
+    #[test]
+    fn named_placeholders() {
+        let values = values([("percent", 7.into()), ("icon", "🔅".into())]);
+
+        assert_eq!("7🔅", render("{percent}{icon}", &values));
+        assert_eq!(" 7🔅", render("{percent:>2}{icon}", &values));
+        assert_eq!("[7  ]", render("[{percent:<3}]", &values));
+        assert_eq!("[ 7 ]", render("[{percent:^3}]", &values));
+        assert_eq!("{7}", render("{{{percent}}}", &values));
+        assert_eq!("x=", render("x={missing}", &values));
+    }
+}
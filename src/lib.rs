@@ -1,13 +1,22 @@
 pub mod bar;
 pub mod battery;
 pub mod brightness;
+pub mod cli;
+pub mod click;
 pub mod config;
 pub mod err;
+pub mod format;
 pub mod logging;
 pub mod nine;
+pub mod output;
 pub mod pulse;
 pub mod rpc;
+pub mod rule;
+pub mod schema;
+pub mod signal;
 pub mod sway;
 #[cfg(test)]
 mod tests;
+pub mod time;
 pub mod volume;
+pub mod widget;
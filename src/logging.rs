@@ -13,7 +13,7 @@ use tracing_subscriber::registry::LookupSpan;
 
 pub fn init_logging(app_name: &str) -> WorkerGuard {
     let default_level = Level::INFO;
-    let log_dir = get_log_dir(app_name);
+    let log_dir = get_cache_dir(app_name);
     let mut file_name = app_name.to_string();
     file_name.push_str(".log");
 
@@ -43,7 +43,8 @@ pub fn init_logging(app_name: &str) -> WorkerGuard {
     guard
 }
 
-fn get_log_dir(app_name: &str) -> Box<Path> {
+/// Directory for logs and state kept across restarts.
+pub fn get_cache_dir(app_name: &str) -> Box<Path> {
     // Look for APPNAME_DEV_DIR environment variable to override default
     let mut dev_dir = app_name.to_uppercase();
     dev_dir.push_str("_DEV_DIR");
@@ -1,124 +1,1571 @@
-const WORKSPACE_MAP: [(i32, Position); 9] = [
-    (2, TopLeft),
-    (1, MiddleLeft),
-    (7, BottomLeft),
-    (3, TopMiddle),
-    (5, MiddleMiddle),
-    (8, BottomMiddle),
-    (4, TopRight),
-    (6, MiddleRight),
-    (0, BottomRight),
-];
-
-trait PositionNumMap {
-    fn position_to_num(&self, position: Position) -> i32;
-    fn num_to_position(&self, num: i32) -> Position;
-}
-
-impl PositionNumMap for [(i32, Position); 9] {
-    fn position_to_num(&self, position: Position) -> i32 {
-        let num = self
-            .iter()
-            .find_map(|(n, p)| if *p == position { Some(n) } else { None });
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-        if let Some(num) = num {
-            *num
-        } else {
-            panic!()
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use tracing::warn;
+
+use crate::{
+    click::run_action,
+    err::Res,
+    format,
+    widget::{self, Value, Values, Widget},
+};
+
+/// Workspaces laid out in rows and columns, configured as `[nine]` and per
+/// sway output as `[nine.outputs.<name>]`; see `examples/barnine.toml`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Grid {
+    pub rows: usize,
+    pub cols: usize,
+    pub cells: Vec<Cell>,
+    pub horizontal: Edge,
+    pub vertical: Edge,
+    pub on_edge: Option<String>,
+    pub follow: bool,
+    #[serde(deserialize_with = "from_one")]
+    pub start: Position,
+    pub jump: bool,
+    pub pages: usize,
+    pub page_offset: i32,
+    pub outputs: BTreeMap<String, Grid>,
+}
+
+/// How the `nine` widget splits into blocks that can be clicked to go to a
+/// cell, configured on its `[[bar]]` entry as e.g. `split = "cells"`.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    /// A block per column, going to the same row
+    Columns,
+    /// A block per cell of the page
+    Cells,
+}
+
+/// What a move past the edge of the grid does.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    /// Come back in on the other side of the same row or column
+    #[default]
+    Wrap,
+    /// Stay put
+    Stop,
+    /// Go on to the next row or column, like reading order
+    Spill,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            rows: 3,
+            cols: 3,
+            cells: IntoIterator::into_iter([2, 3, 4, 1, 5, 6, 7, 8, 0])
+                .map(Cell::Num)
+                .collect(),
+            horizontal: Edge::default(),
+            vertical: Edge::default(),
+            on_edge: None,
+            follow: false,
+            start: Position::default(),
+            jump: true,
+            pages: 1,
+            page_offset: 10,
+            outputs: BTreeMap::new(),
+        }
+    }
+}
+
+impl Grid {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = self.check("nine");
+        for (output, grid) in &self.outputs {
+            let name = format!("nine.outputs.{}", output);
+            errors.extend(grid.check(&name));
+            if !grid.outputs.is_empty() {
+                errors.push(format!("{}: `outputs` only works in `[nine]`", name));
+            }
         }
+        errors
     }
 
-    fn num_to_position(&self, num: i32) -> Position {
-        let name = self
+    fn check(&self, name: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.rows == 0 || self.cols == 0 {
+            errors.push(format!("{}: `rows` and `cols` should be at least 1", name));
+        } else if self.cells.len() != self.rows * self.cols {
+            errors.push(format!(
+                "{}: expected {} cells for {} rows and {} cols, found {}",
+                name,
+                self.rows * self.cols,
+                self.rows,
+                self.cols,
+                self.cells.len()
+            ));
+        } else if self.cell(self.start).is_none() {
+            errors.push(format!(
+                "{}: `start` should be a cell within {} rows and {} cols, counted from 1",
+                name, self.rows, self.cols
+            ));
+        }
+        if self.pages == 0 {
+            errors.push(format!("{}: `pages` should be at least 1", name));
+        }
+        let mut nums = HashSet::new();
+        let mut repeated: Vec<i32> = self
+            .cells
             .iter()
-            .find_map(|(n, p)| if *n == num { Some(p) } else { None });
+            .filter_map(Cell::num)
+            .filter(|num| !nums.insert(*num))
+            .collect();
+        repeated.sort_unstable();
+        repeated.dedup();
+        let mut nums = HashSet::new();
+        let mut page_nums = (0..self.pages).flat_map(|page| {
+            self.cells
+                .iter()
+                .filter_map(move |cell| cell.on_page(page, self.page_offset).num())
+        });
+        if !repeated.is_empty() {
+            let repeated: Vec<_> = repeated.iter().map(i32::to_string).collect();
+            errors.push(format!(
+                "{}: workspace numbers repeat in `cells`: {}",
+                name,
+                repeated.join(", ")
+            ));
+        } else if self.pages > 1 && !page_nums.all(|num| nums.insert(num)) {
+            errors.push(format!(
+                "{}: workspace numbers repeat across pages, `page_offset` should be higher",
+                name
+            ));
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            if let Cell::Table(table) = cell {
+                if table.num.is_some() == table.name.is_some() {
+                    errors.push(format!(
+                        "{}: cell {} should have either `num` or `name`",
+                        name,
+                        i + 1
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Grid of a sway output, which is this one unless it has its own.
+    pub fn for_output(&self, output: &str) -> &Grid {
+        self.outputs.get(output).unwrap_or(self)
+    }
+
+    pub fn cell(&self, position: Position) -> Option<Cell> {
+        if position.row >= self.rows || position.col >= self.cols || position.page >= self.pages {
+            return None;
+        }
+        let cell = self.cells.get(position.row * self.cols + position.col)?;
+        Some(cell.on_page(position.page, self.page_offset))
+    }
+
+    /// Position of a workspace, if it's on any page of the grid.
+    pub fn find(&self, workspace: &Workspace) -> Option<Position> {
+        (0..self.pages).find_map(|page| {
+            let index = self
+                .cells
+                .iter()
+                .position(|cell| cell.on_page(page, self.page_offset).matches(workspace))?;
+            self.position(index, page)
+        })
+    }
+
+    fn position(&self, index: usize, page: usize) -> Option<Position> {
+        if self.cols == 0 {
+            return None;
+        }
+        Some(Position {
+            row: index / self.cols,
+            col: index % self.cols,
+            page,
+        })
+    }
+
+    /// Whether a move would go past the edge of the grid.
+    pub fn at_edge(&self, position: Position, cmd: &NineCmd) -> bool {
+        match cmd {
+            MoveLeft => position.col == 0,
+            MoveRight => position.col + 1 >= self.cols,
+            MoveUp => position.row == 0,
+            MoveDown => position.row + 1 >= self.rows,
+            _ => false,
+        }
+    }
+
+    /// Position after a move, with the edge modes applied.
+    pub fn step(&self, position: Position, cmd: &NineCmd) -> Position {
+        let Position { row, col, page } = position;
+        let (rows, cols) = (self.rows.max(1), self.cols.max(1));
+        let back = |i: usize, n: usize| (i + n - 1) % n;
+        let ahead = |i: usize, n: usize| (i + 1) % n;
+        let edge = match cmd {
+            MoveLeft | MoveRight => self.horizontal,
+            MoveUp | MoveDown => self.vertical,
+            _ => return position,
+        };
+        if edge == Edge::Stop && self.at_edge(position, cmd) {
+            return position;
+        }
+        let spill = edge == Edge::Spill && self.at_edge(position, cmd);
+
+        match cmd {
+            MoveLeft => Position {
+                row: if spill { back(row, rows) } else { row },
+                col: back(col, cols),
+                page,
+            },
+            MoveRight => Position {
+                row: if spill { ahead(row, rows) } else { row },
+                col: ahead(col, cols),
+                page,
+            },
+            MoveUp => Position {
+                row: back(row, rows),
+                col: if spill { back(col, cols) } else { col },
+                page,
+            },
+            MoveDown => Position {
+                row: ahead(row, rows),
+                col: if spill { ahead(col, cols) } else { col },
+                page,
+            },
+            _ => position,
+        }
+    }
+
+    /// Position of the cell with this workspace number, name or label,
+    /// looking on `page` first.
+    pub fn find_cell(&self, name: &str, page: usize) -> Option<Position> {
+        let pages = self.pages.max(1);
+        (0..pages).map(|i| (page + i) % pages).find_map(|page| {
+            let index = self
+                .cells
+                .iter()
+                .position(|cell| cell.on_page(page, self.page_offset).is_called(name))?;
+            self.position(index, page)
+        })
+    }
+
+    /// Position on another page, counted from 0 and wrapping around.
+    pub fn turn(&self, position: Position, cmd: &NineCmd) -> Option<Position> {
+        let pages = self.pages.max(1);
+        let page = match cmd {
+            PageNext => (position.page + 1) % pages,
+            PagePrev => (position.page + pages - 1) % pages,
+            Page(page) if *page < pages => *page,
+            _ => return None,
+        };
+        Some(Position { page, ..position })
+    }
+
+    /// Sway commands that swap the windows on the workspaces of two cells.
+    /// The workspaces are renamed rather than the windows moved, so layouts
+    /// are kept, and each cell keeps the name it had.
+    pub fn swap(
+        &self,
+        from: Position,
+        to: Position,
+        workspaces: &[WorkspaceState],
+    ) -> Option<String> {
+        let existing = |position: Position| {
+            workspaces
+                .iter()
+                .map(|state| &state.workspace)
+                .find(|workspace| self.find(workspace) == Some(position))
+                .map(|workspace| workspace.name.clone())
+        };
+        let from_name = existing(from).or_else(|| self.cell(from).map(|cell| cell.name()))?;
+        let to_cell = self.cell(to)?;
+        let rename =
+            |old: &str, new: &str| format!("rename workspace {} to {}", quote(old), quote(new));
+
+        // Through a spare name, as sway won't rename onto a taken one
+        const SPARE: &str = "barnine-swap";
+        Some(match existing(to) {
+            Some(to_name) => [
+                rename(&from_name, SPARE),
+                rename(&to_name, &from_name),
+                rename(SPARE, &to_name),
+            ]
+            .join("; "),
+            None => rename(&from_name, &to_cell.name()),
+        })
+    }
+
+    /// The whole grid with a symbol for what's on each cell's workspace, with
+    /// the rows side by side.
+    pub fn minimap(&self, page: usize, workspaces: &[WorkspaceState], symbols: &Minimap) -> String {
+        let cols = self.cols.max(1);
+        self.cells
+            .chunks(cols)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| {
+                        let cell = cell.on_page(page, self.page_offset);
+                        let state = workspaces.iter().find(|w| cell.matches(&w.workspace));
+                        match state {
+                            Some(state) if state.urgent => symbols.urgent.as_str(),
+                            Some(state) if state.focused => symbols.focused.as_str(),
+                            Some(state) if state.windows > 0 => symbols.occupied.as_str(),
+                            _ => symbols.empty.as_str(),
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(&symbols.separator)
+    }
 
-        if let Some(name) = name {
-            *name
+    /// The position as one character per column, with the row shown in the
+    /// current column, e.g. `T__` for the top left of a 3×3 grid.  With pages
+    /// the page follows, like `T__ 2`.
+    pub fn label(&self, position: Position) -> String {
+        // ⮰ ⭦
+        let label: String = (0..self.cols)
+            .map(|col| self.column_label(position, col))
+            .collect();
+        if self.pages > 1 {
+            format!("{} {}", label, position.page + 1)
         } else {
-            panic!()
+            label
+        }
+    }
+
+    /// What `label` shows for a column: the row if it's the current
+    /// column, and otherwise `_`.
+    fn column_label(&self, position: Position, col: usize) -> String {
+        if col != position.col {
+            return "_".to_string();
+        }
+        match (self.rows, position.row) {
+            (1..=3, 0) => "T".to_string(),
+            (3, 1) => "M".to_string(),
+            (3, 2) | (2, 1) => "B".to_string(),
+            (_, row) => (row + 1).to_string(),
+        }
+    }
+}
+
+/// A workspace in the grid, by number or by name, or as a table with a
+/// label and a command to run on entering it while it's empty.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Cell {
+    Num(i32),
+    Name(String),
+    Table(CellTable),
+}
+
+/// A cell configured as e.g. `{ num = 1, label = "web", exec = "firefox" }`,
+/// or with `name` instead of `num`.  Numbered workspaces get the label in
+/// their name, like `1:web`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CellTable {
+    pub num: Option<i32>,
+    pub name: Option<String>,
+    pub label: Option<String>,
+    pub exec: Option<String>,
+}
+
+impl Cell {
+    /// Sway command that shows the workspace.
+    pub fn command(&self) -> String {
+        match self.num() {
+            Some(num) if self.label().is_none() => format!("workspace number {}", num),
+            // Goes to the workspace with the number, or creates it with the label
+            Some(_) => format!("workspace number {}", quote(&self.name())),
+            None => format!("workspace {}", quote(&self.name())),
+        }
+    }
+
+    /// Name of the workspace that sway creates for the cell.
+    pub fn name(&self) -> String {
+        match self {
+            Cell::Num(num) => num.to_string(),
+            Cell::Name(name) => name.clone(),
+            Cell::Table(table) => match (table.num, &table.label, &table.name) {
+                (Some(num), Some(label), _) => format!("{}:{}", num, label),
+                (Some(num), None, _) => num.to_string(),
+                (None, _, name) => name.clone().unwrap_or_default(),
+            },
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Cell::Table(table) => table.label.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Shell command to run on entering the workspace while it's empty.
+    pub fn exec(&self) -> Option<&str> {
+        match self {
+            Cell::Table(table) => table.exec.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether `goto` with this name means the cell: its workspace name,
+    /// number or label.
+    fn is_called(&self, name: &str) -> bool {
+        self.name() == name
+            || self.label() == Some(name)
+            || self.num().map(|num| num.to_string()).as_deref() == Some(name)
+    }
+
+    /// The cell as repeated on a page, numbered `offset` higher for each
+    /// page.
+    fn on_page(&self, page: usize, offset: i32) -> Cell {
+        if page == 0 {
+            return self.clone();
+        }
+        let shift = page as i32 * offset;
+        let name = |name: &str| format!("{} {}", name, page + 1);
+        match self {
+            Cell::Num(num) => Cell::Num(num + shift),
+            Cell::Name(n) => Cell::Name(name(n)),
+            Cell::Table(table) => Cell::Table(CellTable {
+                num: table.num.map(|num| num + shift),
+                name: table.name.as_deref().map(name),
+                ..table.clone()
+            }),
+        }
+    }
+
+    fn num(&self) -> Option<i32> {
+        match self {
+            Cell::Num(num) => Some(*num),
+            Cell::Name(_) => None,
+            Cell::Table(table) => table.num,
+        }
+    }
+
+    /// Sway command that moves the focused window to the workspace.
+    pub fn carry_command(&self) -> String {
+        format!("move container to {}", self.command())
+    }
+
+    fn matches(&self, workspace: &Workspace) -> bool {
+        match self.num() {
+            Some(num) => workspace.num == Some(num),
+            None => self.name() == workspace.name,
         }
     }
 }
 
+/// A workspace name quoted for sway commands.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\\\""))
+}
+
+/// The workspace that sway reports as focused.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Workspace {
+    pub num: Option<i32>,
+    pub name: String,
+    pub output: String,
+}
+
 #[derive(Debug)]
 pub enum NineCmd {
     MoveLeft,
     MoveRight,
     MoveUp,
     MoveDown,
-    MovedTo(i32),
-}
-
-#[derive(Copy, Clone, Debug, Default, PartialEq)]
-pub enum Position {
-    #[default]
-    TopLeft,
-    MiddleLeft,
-    BottomLeft,
-    TopMiddle,
-    MiddleMiddle,
-    BottomMiddle,
-    TopRight,
-    MiddleRight,
-    BottomRight,
+    MovedTo(Workspace),
+    /// Go back to the cell visited before
+    Back,
+    /// Undo `Back`
+    Forward,
+    /// Toggle between the two most recent cells
+    Last,
+    /// Jump straight to a cell
+    GoTo(Position),
+    /// Jump straight to a cell of this output's grid, as clicked on the bar
+    GoToOn(String, Position),
+    /// Jump to the cell with this workspace number or name
+    GoToCell(String),
+    /// Go to the same cell on the next page
+    PageNext,
+    /// Go to the same cell on the previous page
+    PagePrev,
+    /// Go to the same cell on a page, counted from 0
+    Page(usize),
+    /// The `exec` of the cell with this workspace name failed
+    ExecFailed(String),
 }
 
 use NineCmd::*;
-use Position::*;
 
-impl From<i32> for Position {
-    fn from(value: i32) -> Self {
-        WORKSPACE_MAP.num_to_position(value)
+impl NineCmd {
+    /// Direction of a move, as given to the `on_edge` command.
+    pub fn direction(&self) -> Option<&'static str> {
+        match self {
+            MoveLeft => Some("left"),
+            MoveRight => Some("right"),
+            MoveUp => Some("up"),
+            MoveDown => Some("down"),
+            _ => None,
+        }
     }
 }
 
-impl Position {
-    pub fn num(&self) -> i32 {
-        WORKSPACE_MAP.position_to_num(*self)
+/// Where each sway output is in its grid, and the moves between cells.
+/// Moves give the sway command that makes them, for the bar to run.
+#[derive(Debug, Default)]
+pub struct Nine {
+    grid: Grid,
+    minimap: Minimap,
+    /// Position in the grid of each sway output
+    positions: HashMap<String, Position>,
+    /// Workspace names of outputs focused on a workspace outside their grid
+    off_grid: HashMap<String, String>,
+    focused_output: String,
+    /// Output whose grid the bar shows, instead of the focused one
+    bar_output: Option<String>,
+    /// Where positions are saved, to pick up again after a restart
+    state_file: Option<PathBuf>,
+    workspaces: Vec<WorkspaceState>,
+    /// Workspaces whose `exec` ran, until they get a window
+    launched: HashSet<String>,
+    history: History,
+}
+
+impl Nine {
+    /// Show the grid of this sway output, instead of the focused one.
+    pub fn set_bar_output(&mut self, output: Option<String>) {
+        self.bar_output = output;
     }
 
-    pub fn map_cmd(&self, cmd: NineCmd) -> Self {
-        let grid = [
-            [TopLeft, MiddleLeft, BottomLeft],
-            [TopMiddle, MiddleMiddle, BottomMiddle],
-            [TopRight, MiddleRight, BottomRight],
-        ];
-        let [col, row] = match self {
-            TopLeft => [0, 0],
-            MiddleLeft => [0, 1],
-            BottomLeft => [0, 2],
-            TopMiddle => [1, 0],
-            MiddleMiddle => [1, 1],
-            BottomMiddle => [1, 2],
-            TopRight => [2, 0],
-            MiddleRight => [2, 1],
-            BottomRight => [2, 2],
+    /// Save positions to this file, starting from the ones saved before.
+    pub fn set_state_file(&mut self, path: PathBuf) {
+        self.positions = read_positions(&path);
+        self.state_file = Some(path);
+    }
+
+    /// Take the grids and minimap symbols of a new config, forgetting
+    /// positions that are no longer in their grid.
+    pub fn load_config(&mut self, grid: &Grid, minimap: &Minimap) {
+        self.grid = grid.clone();
+        self.minimap = minimap.clone();
+        let grid = &self.grid;
+        self.positions
+            .retain(|output, position| grid.for_output(output).cell(*position).is_some());
+    }
+
+    pub fn set_workspaces(&mut self, workspaces: Vec<WorkspaceState>) {
+        let busy: HashSet<_> = workspaces
+            .iter()
+            .filter(|state| state.windows > 0)
+            .map(|state| &state.workspace.name)
+            .collect();
+        self.launched.retain(|name| !busy.contains(name));
+        self.workspaces = workspaces;
+    }
+
+    fn grid(&self, output: &str) -> &Grid {
+        self.grid.for_output(output)
+    }
+
+    fn position(&self, output: &str) -> Position {
+        let start = self.grid(output).start;
+        self.positions.get(output).copied().unwrap_or(start)
+    }
+
+    /// Output whose grid the bar shows.
+    pub fn shown_output(&self) -> &str {
+        self.bar_output.as_ref().unwrap_or(&self.focused_output)
+    }
+
+    fn save_positions(&self) {
+        if let Some(ref path) = self.state_file {
+            if let Err(err) = write_positions(path, &self.positions) {
+                warn!("Could not save grid positions: {:?}", err);
+            }
+        }
+    }
+
+    /// Go to the saved or configured cell of the output with the focused
+    /// workspace, or just follow that workspace without `jump`.
+    pub fn start(&mut self, workspace: Workspace) -> Option<String> {
+        if !self.grid(&workspace.output).jump {
+            self.moved_to(workspace);
+            return None;
+        }
+        let output = workspace.output;
+        let position = self.position(&output);
+        self.show(&output, position)
+    }
+
+    /// Follow focus or move around the grid.
+    pub fn go(&mut self, cmd: NineCmd) -> Option<String> {
+        let target = match cmd {
+            MovedTo(workspace) => {
+                self.moved_to(workspace);
+                return None;
+            }
+            ExecFailed(name) => {
+                self.launched.remove(&name);
+                return None;
+            }
+            Back | Forward | Last => self.history.go(&cmd),
+            GoToOn(output, position) => {
+                let position = Position {
+                    page: self.position(&output).page,
+                    ..position
+                };
+                self.grid(&output).cell(position)?;
+                if output != self.focused_output {
+                    let command = self.show(&output, position)?;
+                    return Some(format!("focus output {}; {}", quote(&output), command));
+                }
+                Some((output, position))
+            }
+            MoveLeft | MoveRight | MoveUp | MoveDown => {
+                if let Some(hook) = self.edge_hook(&cmd) {
+                    run_action(&hook);
+                    return None;
+                }
+                self.target(&cmd)
+                    .map(|position| (self.focused_output.clone(), position))
+            }
+            _ => self
+                .target(&cmd)
+                .map(|position| (self.focused_output.clone(), position)),
+        };
+        let (output, position) = target?;
+        self.show(&output, position)
+    }
+
+    /// Move the focused window to the cell a move leads to.
+    pub fn carry(&mut self, cmd: &NineCmd) -> Option<String> {
+        let output = self.focused_output.clone();
+        let position = self.target(cmd)?;
+        let grid = self.grid(&output);
+        let cell = grid.cell(position)?;
+        let mut command = cell.carry_command();
+        if grid.follow {
+            command = format!("{}; {}", command, cell.command());
+            self.go_to(&output, position);
+        }
+        Some(command)
+    }
+
+    /// Swap the windows of the focused workspace with the cell a move leads
+    /// to, and then show the cell we stay on.
+    pub fn swap(&mut self, cmd: &NineCmd) -> Option<String> {
+        let output = self.focused_output.clone();
+        if self.off_grid.contains_key(&output) {
+            return None;
+        }
+        let current = self.position(&output);
+        let position = self.target(cmd)?;
+        let grid = self.grid(&output);
+        let mut command = grid.swap(current, position, &self.workspaces)?;
+
+        // The focused workspace now goes by the other cell's name
+        if grid.follow {
+            self.go_to(&output, position);
+        } else {
+            command = format!("{}; {}", command, grid.cell(current)?.command());
+        }
+        Some(command)
+    }
+
+    /// The `exec` of a cell we're coming to, unless its workspace has
+    /// windows or the command is still starting up.
+    pub fn exec_on_enter(&mut self, workspace: &Workspace) -> Option<String> {
+        let grid = self.grid(&workspace.output);
+        let exec = grid.cell(grid.find(workspace)?)?.exec()?.to_string();
+        let busy = self
+            .workspaces
+            .iter()
+            .any(|state| state.workspace.name == workspace.name && state.windows > 0);
+        if busy || !self.launched.insert(workspace.name.clone()) {
+            return None;
+        }
+        Some(exec)
+    }
+
+    /// Follow focus to a workspace, which may be on another output.
+    fn moved_to(&mut self, workspace: Workspace) {
+        if let Some(position) = self.grid(&workspace.output).find(&workspace) {
+            self.positions.insert(workspace.output.clone(), position);
+            self.history.visit(&workspace.output, position);
+            self.off_grid.remove(&workspace.output);
+            self.save_positions();
+        } else {
+            self.off_grid
+                .insert(workspace.output.clone(), workspace.name.clone());
+        }
+        self.focused_output = workspace.output;
+    }
+
+    /// Move to a cell of an output's grid, before telling sway to.
+    fn go_to(&mut self, output: &str, position: Position) {
+        self.positions.insert(output.to_string(), position);
+        self.history.visit(output, position);
+        self.off_grid.remove(output);
+        self.save_positions();
+        self.focused_output = output.to_string();
+    }
+
+    /// Move to a cell, with the sway command that shows it.
+    fn show(&mut self, output: &str, position: Position) -> Option<String> {
+        self.go_to(output, position);
+        Some(self.grid(output).cell(position)?.command())
+    }
+
+    /// The `on_edge` command to run instead of a move past the edge of the
+    /// focused output's grid, if it has one.
+    fn edge_hook(&self, cmd: &NineCmd) -> Option<String> {
+        let grid = self.grid(&self.focused_output);
+        let current = self.position(&self.focused_output);
+        let hook = grid.on_edge.as_ref()?;
+        let direction = cmd.direction()?;
+        if self.off_grid.contains_key(&self.focused_output) || !grid.at_edge(current, cmd) {
+            return None;
+        }
+        let values = widget::values([("direction", direction.into())]);
+        Some(format::render(hook, &values))
+    }
+
+    /// Position in the grid of the focused output that a move leads to, if
+    /// it leads anywhere.
+    fn target(&self, cmd: &NineCmd) -> Option<Position> {
+        let grid = self.grid(&self.focused_output);
+        let current = self.position(&self.focused_output);
+        let off_grid = self.off_grid.contains_key(&self.focused_output);
+        let target = match cmd {
+            GoTo(position) => Position {
+                page: current.page,
+                ..*position
+            },
+            GoToCell(name) => grid.find_cell(name, current.page)?,
+            PageNext | PagePrev | Page(_) => grid.turn(current, cmd)?,
+            // Relative moves from outside the grid go back to where we left it
+            _ if off_grid => current,
+            _ => grid.step(current, cmd),
+        };
+        grid.cell(target)?;
+        Some(target).filter(|position| off_grid || *position != current)
+    }
+
+    /// Values of the `nine` widget, for the output the bar shows.
+    pub fn values(&self) -> Values {
+        let output = self.shown_output();
+        let grid = self.grid(output);
+        let off_grid = self.off_grid.get(output);
+        let current = self.position(output);
+        let (position, label) = match off_grid {
+            Some(name) => (name.clone(), String::new()),
+            None => {
+                let cell = grid.cell(current);
+                let label = cell.as_ref().and_then(|cell| cell.label());
+                (grid.label(current), label.unwrap_or_default().to_string())
+            }
+        };
+        widget::values([
+            ("position", position.into()),
+            ("label", label.into()),
+            ("page", Value::Int(current.page as i64 + 1)),
+            ("off_grid", off_grid.is_some().into()),
+        ])
+    }
+
+    /// The `minimap` widget's map of the page the bar shows.
+    pub fn minimap(&self) -> String {
+        let output = self.shown_output();
+        let page = self.position(output).page;
+        self.grid(output)
+            .minimap(page, &self.workspaces, &self.minimap)
+    }
+
+    /// Blocks of a split `nine` widget for the output the bar shows: the
+    /// position and text of each cell, and whether we're on it.
+    pub fn split(&self, split: Split) -> Vec<(Position, String, bool)> {
+        let output = self.shown_output();
+        let grid = self.grid(output);
+        let current = self.position(output);
+        let off_grid = self.off_grid.contains_key(output);
+        let rows = match split {
+            Split::Columns => current.row..current.row + 1,
+            Split::Cells => 0..grid.rows,
         };
 
+        let mut cells = Vec::new();
+        for row in rows {
+            for col in 0..grid.cols {
+                let position = Position {
+                    row,
+                    col,
+                    ..current
+                };
+                let text = match split {
+                    _ if off_grid => "_".to_string(),
+                    Split::Columns => grid.column_label(current, col),
+                    Split::Cells => match grid.cell(position) {
+                        Some(cell) => cell
+                            .label()
+                            .map(String::from)
+                            .unwrap_or_else(|| cell.name()),
+                        None => continue,
+                    },
+                };
+                cells.push((position, text, !off_grid && position == current));
+            }
+        }
+        cells
+    }
+}
+
+/// Visited cells, as sway output name and position, for going back and
+/// forward like in a browser.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: VecDeque<(String, Position)>,
+    current: usize,
+}
+
+impl History {
+    /// Number of cells remembered.
+    const LEN: usize = 50;
+
+    /// Remember a cell, dropping anything ahead of the current one.
+    pub fn visit(&mut self, output: &str, position: Position) {
+        let entry = (output.to_string(), position);
+        if self.entries.get(self.current) == Some(&entry) {
+            return;
+        }
+        self.entries.truncate(self.current + 1);
+        self.entries.push_back(entry);
+        if self.entries.len() > Self::LEN {
+            self.entries.pop_front();
+        }
+        self.current = self.entries.len() - 1;
+    }
+
+    /// Cell to go to for `Back`, `Forward` or `Last`.
+    pub fn go(&mut self, cmd: &NineCmd) -> Option<(String, Position)> {
         match cmd {
-            MoveLeft => grid[(col + 2) % 3][row],
-            MoveRight => grid[(col + 1) % 3][row],
-            MoveUp => grid[col][(row + 2) % 3],
-            MoveDown => grid[col][(row + 1) % 3],
-            MovedTo(_) => panic!(),
+            Back if self.current > 0 => self.current -= 1,
+            Forward if self.current + 1 < self.entries.len() => self.current += 1,
+            Last if self.current > 0 => return self.entries.get(self.current - 1).cloned(),
+            _ => return None,
         }
+        self.entries.get(self.current).cloned()
     }
 }
 
-impl std::fmt::Display for Position {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // ⮰ ⭦
-        f.write_str(match self {
-            TopLeft => "T__",
-            MiddleLeft => "M__",
-            BottomLeft => "B__",
-            TopMiddle => "_T_",
-            MiddleMiddle => "_M_",
-            BottomMiddle => "_B_",
-            TopRight => "__T",
-            MiddleRight => "__M",
-            BottomRight => "__B",
-        })
+/// What's on a workspace, as shown by the minimap.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkspaceState {
+    pub workspace: Workspace,
+    pub windows: usize,
+    pub focused: bool,
+    pub urgent: bool,
+}
+
+/// Symbols of the `minimap` widget, configured as e.g. `[minimap]` with
+/// `occupied = "●"`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Minimap {
+    pub empty: String,
+    pub occupied: String,
+    pub focused: String,
+    pub urgent: String,
+    /// Put between rows
+    pub separator: String,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            empty: "□".into(),
+            occupied: "■".into(),
+            focused: "▣".into(),
+            urgent: "◈".into(),
+            separator: " ".into(),
+        }
+    }
+}
+
+/// A cell of the grid, counted from the top left.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
+    #[serde(default)]
+    pub page: usize,
+}
+
+/// A position given as row and column counted from 1, like pages, e.g. `2 3`
+/// for the right of the middle row of a 3×3 grid, on the first page.
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace().map(str::parse::<usize>);
+        match (words.next(), words.next(), words.next()) {
+            (Some(Ok(row)), Some(Ok(col)), None) if row > 0 && col > 0 => Ok(Position {
+                row: row - 1,
+                col: col - 1,
+                page: 0,
+            }),
+            _ => Err(format!("expected a row and column from 1, found `{}`", s)),
+        }
+    }
+}
+
+/// A position in the config, with its row, column and page counted from 1
+/// like `goto` and `page` count them.
+fn from_one<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Position, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct FromOne {
+        row: usize,
+        col: usize,
+        page: Option<usize>,
+    }
+
+    let FromOne { row, col, page } = FromOne::deserialize(deserializer)?;
+    match (
+        row.checked_sub(1),
+        col.checked_sub(1),
+        page.unwrap_or(1).checked_sub(1),
+    ) {
+        (Some(row), Some(col), Some(page)) => Ok(Position { row, col, page }),
+        _ => Err(D::Error::custom("rows, columns and pages count from 1")),
+    }
+}
+
+/// Positions by sway output saved by `write_positions`, or none if they
+/// can't be read.
+fn read_positions(path: &Path) -> HashMap<String, Position> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Save positions by sway output, to pick up again after a restart.
+fn write_positions(path: &Path, positions: &HashMap<String, Position>) -> Res<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string(positions).map_err(io::Error::from)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Current position in the grid, kept up to date by the bar.
+#[derive(Debug, Default)]
+pub struct NineWidget(Values);
+
+impl Widget for NineWidget {
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        self.0.clone()
+    }
+
+    fn default_format(&self) -> &str {
+        "{position}"
+    }
+}
+
+/// The whole grid with what's on each workspace, kept up to date by the bar.
+#[derive(Debug, Default)]
+pub struct MinimapWidget(Values);
+
+impl Widget for MinimapWidget {
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        self.0.clone()
+    }
+
+    fn default_format(&self) -> &str {
+        "{map}"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempfile::tempdir;
+
+    use super::{
+        read_positions, write_positions, Cell, Edge, Grid, History, Minimap, Nine, NineCmd::*,
+        Position, Split, Workspace, WorkspaceState,
+    };
+    use crate::widget::Value;
+
+    fn at(row: usize, col: usize) -> Position {
+        Position { row, col, page: 0 }
+    }
+
+    /// Grid state with the grid of a `[nine]` table, on no cell yet.
+    fn nine_from(toml: &str) -> Nine {
+        let grid: Grid = toml::from_str(toml).unwrap();
+        let mut nine = Nine::default();
+        nine.load_config(&grid, &Minimap::default());
+        nine
+    }
+
+    /// A workspace as sway reports it, numbered if its name starts with one.
+    fn workspace(name: &str, output: &str) -> Workspace {
+        Workspace {
+            num: name.split(':').next().and_then(|num| num.parse().ok()),
+            name: name.into(),
+            output: output.into(),
+        }
+    }
+
+    /// The position shown by the `nine` widget.
+    fn position(nine: &Nine) -> String {
+        nine.values()["position"].to_string()
+    }
+
+    #[test]
+    fn default_grid() {
+        let grid = Grid::default();
+        assert!(grid.validate().is_empty());
+        assert_eq!(Some(Cell::Num(2)), grid.cell(Position::default()));
+        assert_eq!(Some(Cell::Num(0)), grid.cell(at(2, 2)));
+        assert_eq!("T__", grid.label(at(0, 0)));
+        assert_eq!("_M_", grid.label(at(1, 1)));
+        assert_eq!("__B", grid.label(at(2, 2)));
+
+        // Moves wrap around at the edges
+        assert_eq!(at(0, 2), grid.step(at(0, 0), &MoveLeft));
+        assert_eq!(at(2, 0), grid.step(at(0, 0), &MoveUp));
+        assert_eq!(at(1, 0), grid.step(at(0, 0), &MoveDown));
+    }
+
+    #[test]
+    fn configured_grid() {
+        let grid: Grid = toml::from_str(concat!(
+            "rows = 2\n",
+            "cols = 4\n",
+            "cells = [1, 2, 3, 4, 5, 6, 7, \"mail\"]\n",
+        ))
+        .unwrap();
+        assert!(grid.validate().is_empty());
+        assert_eq!("___B", grid.label(at(1, 3)));
+        assert_eq!(at(1, 0), grid.step(at(1, 3), &MoveRight));
+
+        let mail = Workspace {
+            num: None,
+            name: "mail".into(),
+            ..Default::default()
+        };
+        assert_eq!(Some(at(1, 3)), grid.find(&mail));
+        assert_eq!("workspace \"mail\"", grid.cell(at(1, 3)).unwrap().command());
+        let six = Workspace {
+            num: Some(6),
+            name: "6: web".into(),
+            ..Default::default()
+        };
+        assert_eq!(Some(at(1, 1)), grid.find(&six));
+        assert_eq!("workspace number 6", grid.cell(at(1, 1)).unwrap().command());
+        assert_eq!(
+            "move container to workspace number 6",
+            grid.cell(at(1, 1)).unwrap().carry_command()
+        );
+
+        let grid: Grid = toml::from_str("rows = 4\ncols = 4").unwrap();
+        assert_eq!(1, grid.validate().len());
+
+        let grid: Grid =
+            toml::from_str("cells = [1, 2, 3, { label = \"x\" }, 5, 6, 7, 8, 9]").unwrap();
+        assert_eq!(1, grid.validate().len());
+
+        let grid: Grid =
+            toml::from_str("cells = [1, 2, 3, 4, 5, 6, 7, { num = 2, label = \"x\" }, 9]").unwrap();
+        assert_eq!(
+            vec!["nine: workspace numbers repeat in `cells`: 2"],
+            grid.validate()
+        );
+
+        let grid: Grid = toml::from_str("start = { row = 2, col = 3 }").unwrap();
+        assert!(grid.validate().is_empty());
+        assert_eq!(at(1, 2), grid.start);
+        let grid: Grid = toml::from_str("start = { row = 4, col = 1 }").unwrap();
+        assert_eq!(1, grid.validate().len());
+        let err = toml::from_str::<Grid>("start = { row = 1, col = 0 }").unwrap_err();
+        assert!(err.to_string().contains("count from 1"), "{}", err);
+    }
+
+    #[test]
+    fn goto_and_swap() {
+        assert_eq!(Ok(at(1, 2)), " 2  3 ".parse());
+        assert!("0 1".parse::<Position>().is_err());
+        assert!("1".parse::<Position>().is_err());
+        assert!("1 2 3".parse::<Position>().is_err());
+        assert!("mail".parse::<Position>().is_err());
+
+        let grid: Grid = toml::from_str("rows = 1\ncols = 3\ncells = [1, 2, \"mail\"]").unwrap();
+        assert_eq!(Some(at(0, 1)), grid.find_cell("2", 0));
+        assert_eq!(Some(at(0, 2)), grid.find_cell("mail", 0));
+        assert_eq!(None, grid.find_cell("3", 0));
+
+        let state = |num: Option<i32>, name: &str| WorkspaceState {
+            workspace: Workspace {
+                num,
+                name: name.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let workspaces = [state(Some(1), "1:web"), state(None, "mail")];
+        assert_eq!(
+            Some(concat!(
+                r#"rename workspace "1:web" to "barnine-swap"; "#,
+                r#"rename workspace "mail" to "1:web"; "#,
+                r#"rename workspace "barnine-swap" to "mail""#,
+            )),
+            grid.swap(at(0, 0), at(0, 2), &workspaces).as_deref()
+        );
+
+        // Workspaces that don't exist yet are just named
+        assert_eq!(
+            Some(r#"rename workspace "1:web" to "2""#),
+            grid.swap(at(0, 0), at(0, 1), &workspaces).as_deref()
+        );
+        assert_eq!(None, grid.swap(at(0, 0), at(1, 0), &workspaces));
+    }
+
+    #[test]
+    fn labeled_cells() {
+        let grid: Grid = toml::from_str(concat!(
+            "rows = 1\n",
+            "cols = 3\n",
+            "cells = [\n",
+            "    { num = 1, label = \"web\", exec = \"firefox\" },\n",
+            "    { name = \"chat\", label = \"💬\" },\n",
+            "    { num = 3 },\n",
+            "]\n",
+        ))
+        .unwrap();
+        assert!(grid.validate().is_empty());
+
+        let web = grid.cell(at(0, 0)).unwrap();
+        assert_eq!("workspace number \"1:web\"", web.command());
+        assert_eq!(Some("web"), web.label());
+        assert_eq!(Some("firefox"), web.exec());
+        let chat = grid.cell(at(0, 1)).unwrap();
+        assert_eq!("workspace \"chat\"", chat.command());
+        assert_eq!(Some("💬"), chat.label());
+        assert_eq!(None, chat.exec());
+        assert_eq!("workspace number 3", grid.cell(at(0, 2)).unwrap().command());
+
+        // Found by number whatever the workspace is called now
+        let one = Workspace {
+            num: Some(1),
+            name: "1".into(),
+            ..Default::default()
+        };
+        assert_eq!(Some(at(0, 0)), grid.find(&one));
+        for name in ["1", "1:web", "web"] {
+            assert_eq!(Some(at(0, 0)), grid.find_cell(name, 0));
+        }
+        assert_eq!(Some(at(0, 1)), grid.find_cell("💬", 0));
+    }
+
+    #[test]
+    fn grid_pages() {
+        let grid: Grid = toml::from_str(concat!(
+            "rows = 1\n",
+            "cols = 3\n",
+            "cells = [1, \"mail\", { num = 3, label = \"web\" }]\n",
+            "pages = 3\n",
+        ))
+        .unwrap();
+        assert!(grid.validate().is_empty());
+        let on = |page: usize, col: usize| Position { row: 0, col, page };
+
+        assert_eq!(Some(Cell::Num(21)), grid.cell(on(2, 0)));
+        assert_eq!(Some(Cell::Name("mail 2".into())), grid.cell(on(1, 1)));
+        assert_eq!(
+            "workspace number \"13:web\"",
+            grid.cell(on(1, 2)).unwrap().command()
+        );
+        assert_eq!(None, grid.cell(on(3, 0)));
+        assert_eq!("_T_ 2", grid.label(on(1, 1)));
+
+        assert_eq!(Some(on(2, 0)), grid.find(&workspace("21", "eDP-1")));
+        assert_eq!(Some(on(1, 1)), grid.find(&workspace("mail 2", "eDP-1")));
+        assert_eq!(None, grid.find(&workspace("31", "eDP-1")));
+
+        // Labels are found on the current page first
+        assert_eq!(Some(on(2, 2)), grid.find_cell("web", 2));
+        assert_eq!(Some(on(1, 0)), grid.find_cell("11", 2));
+
+        // Moves stay on the page, while turning keeps the cell
+        assert_eq!(on(2, 1), grid.step(on(2, 0), &MoveRight));
+        assert_eq!(Some(on(0, 2)), grid.turn(on(2, 2), &PageNext));
+        assert_eq!(Some(on(2, 2)), grid.turn(on(0, 2), &PagePrev));
+        assert_eq!(Some(on(1, 2)), grid.turn(on(0, 2), &Page(1)));
+        assert_eq!(None, grid.turn(on(0, 2), &Page(3)));
+
+        let grid: Grid = toml::from_str("pages = 2\npage_offset = 5").unwrap();
+        assert_eq!(1, grid.validate().len());
+    }
+
+    #[test]
+    fn saved_positions() {
+        let path = tempdir()
+            .unwrap()
+            .into_path()
+            .join("barnine")
+            .join("nine.json");
+        assert!(read_positions(&path).is_empty());
+
+        let positions = HashMap::from([("eDP-1".to_string(), at(2, 1))]);
+        write_positions(&path, &positions).unwrap();
+        assert_eq!(positions, read_positions(&path));
+
+        std::fs::write(&path, "garbage").unwrap();
+        assert!(read_positions(&path).is_empty());
+    }
+
+    #[test]
+    fn minimap_cells() {
+        let state = |num: i32, windows: usize, focused: bool, urgent: bool| WorkspaceState {
+            workspace: Workspace {
+                num: Some(num),
+                name: num.to_string(),
+                ..Default::default()
+            },
+            windows,
+            focused,
+            urgent,
+        };
+        let workspaces = vec![
+            state(2, 1, false, false),
+            state(5, 0, true, false),
+            state(0, 3, false, true),
+            state(11, 1, false, false),
+        ];
+        let grid = Grid::default();
+        assert_eq!(
+            "■□□ □▣□ □□◈",
+            grid.minimap(0, &workspaces, &Minimap::default())
+        );
+
+        let symbols: Minimap = toml::from_str("empty = \".\"\nseparator = \"|\"").unwrap();
+        assert_eq!("■..|.▣.|..◈", grid.minimap(0, &workspaces, &symbols));
+    }
+
+    #[test]
+    fn history() {
+        let mut history = History::default();
+        assert_eq!(None, history.go(&Back));
+        for col in 0..3 {
+            history.visit("eDP-1", at(0, col));
+        }
+        history.visit("eDP-1", at(0, 2));
+
+        let back = history.go(&Back).unwrap();
+        assert_eq!(("eDP-1".to_string(), at(0, 1)), back);
+        history.visit(&back.0, back.1);
+        assert_eq!(Some(at(0, 0)), history.go(&Back).map(|(_, p)| p));
+        assert_eq!(None, history.go(&Back));
+        assert_eq!(Some(at(0, 1)), history.go(&Forward).map(|(_, p)| p));
+
+        // Visiting drops the cells ahead
+        history.visit("HDMI-A-1", at(1, 1));
+        assert_eq!(None, history.go(&Forward));
+
+        // Last toggles between the two most recent cells
+        let last = history.go(&Last).unwrap();
+        assert_eq!(("eDP-1".to_string(), at(0, 1)), last);
+        history.visit(&last.0, last.1);
+        assert_eq!(Some(at(1, 1)), history.go(&Last).map(|(_, p)| p));
+
+        // Only the most recent cells are kept
+        for row in 0..History::LEN {
+            history.visit("eDP-1", at(row, 0));
+        }
+        assert_eq!(History::LEN, history.entries.len());
+    }
+
+    #[test]
+    fn output_grids() {
+        let grid: Grid = toml::from_str(concat!(
+            "[outputs.HDMI-A-1]\n",
+            "rows = 1\n",
+            "cols = 2\n",
+            "cells = [11, 12]\n",
+            "[outputs.DP-1]\n",
+            "rows = 1\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            vec!["nine.outputs.DP-1: expected 3 cells for 1 rows and 3 cols, found 9"],
+            grid.validate()
+        );
+
+        assert_eq!(3, grid.for_output("eDP-1").cols);
+        let hdmi = grid.for_output("HDMI-A-1");
+        assert_eq!("_T", hdmi.label(at(0, 1)));
+        assert_eq!(Some(Cell::Num(12)), hdmi.cell(at(0, 1)));
+    }
+
+    #[test]
+    fn edge_modes() {
+        let mut grid: Grid = toml::from_str("horizontal = \"stop\"\nvertical = \"spill\"").unwrap();
+        assert!(grid.at_edge(at(1, 0), &MoveLeft));
+        assert!(!grid.at_edge(at(1, 1), &MoveLeft));
+
+        // Stop at the left and right
+        assert_eq!(at(1, 0), grid.step(at(1, 0), &MoveLeft));
+        assert_eq!(at(1, 2), grid.step(at(1, 2), &MoveRight));
+        assert_eq!(at(1, 1), grid.step(at(1, 2), &MoveLeft));
+
+        // Spill into the next column, and from the last cell to the first
+        assert_eq!(at(0, 1), grid.step(at(2, 0), &MoveDown));
+        assert_eq!(at(2, 0), grid.step(at(0, 1), &MoveUp));
+        assert_eq!(at(0, 0), grid.step(at(2, 2), &MoveDown));
+        assert_eq!(at(1, 0), grid.step(at(0, 0), &MoveDown));
+
+        // Spill along rows in reading order
+        grid.horizontal = Edge::Spill;
+        assert_eq!(at(1, 0), grid.step(at(0, 2), &MoveRight));
+        assert_eq!(at(2, 2), grid.step(at(0, 0), &MoveLeft));
+    }
+
+    #[test]
+    fn nine_targets() {
+        let nine = nine_from("horizontal = \"stop\"");
+
+        // Nowhere to go past a stopping edge
+        assert_eq!(None, nine.target(&MoveLeft));
+        assert_eq!(Some(at(0, 1)), nine.target(&MoveRight));
+        assert_eq!(Some(at(2, 0)), nine.target(&MoveUp));
+
+        // Jumps to cells, by position or workspace
+        let middle = at(1, 1);
+        assert_eq!(Some(middle), nine.target(&GoTo(middle)));
+        assert_eq!(Some(at(2, 2)), nine.target(&GoToCell("0".into())));
+        assert_eq!(None, nine.target(&GoToCell("9".into())));
+        assert_eq!(None, nine.target(&GoTo(at(3, 0))));
+        assert_eq!(None, nine.target(&GoTo(Position::default())));
+    }
+
+    #[tokio::test]
+    async fn edge_hook_instead_of_wrapping() {
+        let mut nine = nine_from("on_edge = \"true {direction}\"");
+        nine.moved_to(workspace("1", "eDP-1"));
+
+        // Past the edge the hook runs, and we stay put
+        assert_eq!(Some("true left".into()), nine.edge_hook(&MoveLeft));
+        assert_eq!(None, nine.go(MoveLeft));
+        assert_eq!(at(1, 0), nine.position("eDP-1"));
+
+        // Within the grid, and for carrying, it's an ordinary move
+        assert_eq!(None, nine.edge_hook(&MoveRight));
+        assert_eq!(Some("workspace number 5".into()), nine.go(MoveRight));
+        assert_eq!(None, nine.edge_hook(&GoTo(at(1, 0))));
+        nine.go(GoTo(at(1, 0)));
+        assert_eq!(
+            Some("move container to workspace number 6".into()),
+            nine.carry(&MoveLeft)
+        );
+    }
+
+    #[test]
+    fn turn_pages() {
+        let mut nine = nine_from("pages = 2");
+        nine.moved_to(workspace("16", "eDP-1"));
+        assert_eq!("__M 2", position(&nine));
+        assert_eq!(Value::Int(2), nine.values()["page"]);
+
+        let page_one = Position {
+            page: 0,
+            ..at(1, 2)
+        };
+        assert_eq!(Some(page_one), nine.target(&PageNext));
+        assert_eq!(Some(page_one), nine.target(&Page(0)));
+        assert_eq!(None, nine.target(&Page(1)));
+        assert_eq!(None, nine.target(&Page(2)));
+
+        // Moves and jumps stay on the page
+        let page_two = |row, col| Position {
+            page: 1,
+            ..at(row, col)
+        };
+        assert_eq!(Some(page_two(1, 0)), nine.target(&MoveRight));
+        assert_eq!(Some(page_two(0, 0)), nine.target(&GoTo(at(0, 0))));
+    }
+
+    #[test]
+    fn swap_with_neighbors() {
+        let mut nine = nine_from("");
+        nine.moved_to(workspace("5", "eDP-1"));
+
+        assert_eq!(
+            Some(r#"rename workspace "5" to "6"; workspace number 5"#.into()),
+            nine.swap(&MoveRight)
+        );
+        assert_eq!(at(1, 1), nine.position("eDP-1"));
+
+        // With `follow` we stay with the windows
+        let grid: Grid = toml::from_str("follow = true").unwrap();
+        nine.load_config(&grid, &Minimap::default());
+        assert_eq!(
+            Some(r#"rename workspace "5" to "6""#.into()),
+            nine.swap(&MoveRight)
+        );
+        assert_eq!(at(1, 2), nine.position("eDP-1"));
+
+        // Nothing to swap from outside the grid
+        nine.moved_to(workspace("9", "eDP-1"));
+        assert!(nine.swap(&MoveRight).is_none());
+    }
+
+    #[test]
+    fn grid_per_output() {
+        let mut nine = nine_from(concat!(
+            "[outputs.HDMI-A-1]\n",
+            "rows = 1\n",
+            "cols = 2\n",
+            "cells = [11, 12]\n",
+        ));
+
+        nine.moved_to(workspace("5", "eDP-1"));
+        assert_eq!("_M_", position(&nine));
+        nine.moved_to(workspace("12", "HDMI-A-1"));
+        assert_eq!("_T", position(&nine));
+        assert_eq!(None, nine.target(&MoveUp));
+        assert_eq!(Some(at(0, 0)), nine.target(&MoveRight));
+
+        // Each output keeps its own position
+        nine.moved_to(workspace("8", "eDP-1"));
+        assert_eq!("_B_", position(&nine));
+        nine.set_bar_output(Some("HDMI-A-1".into()));
+        assert_eq!("_T", position(&nine));
+
+        // Clicks go to the grid the bar shows, not the focused one
+        assert_eq!(
+            Some(r#"focus output "HDMI-A-1"; workspace number 11"#.into()),
+            nine.go(GoToOn("HDMI-A-1".into(), at(0, 0)))
+        );
+        assert_eq!(at(0, 0), nine.position("HDMI-A-1"));
+        assert_eq!(at(2, 1), nine.position("eDP-1"));
+        assert_eq!(None, nine.go(GoToOn("HDMI-A-1".into(), at(1, 0))));
+    }
+
+    #[test]
+    fn restore_positions() {
+        let grid: Grid = toml::from_str("start = { row = 2, col = 2 }").unwrap();
+        let path = tempdir().unwrap().into_path().join("nine.json");
+        let mut nine = Nine::default();
+        nine.set_state_file(path.clone());
+        nine.load_config(&grid, &Minimap::default());
+        assert_eq!(at(1, 1), nine.position("eDP-1"));
+
+        nine.moved_to(workspace("8", "eDP-1"));
+
+        // Saved positions outlast a restart
+        let mut nine = Nine::default();
+        nine.set_state_file(path);
+        nine.load_config(&grid, &Minimap::default());
+        assert_eq!(at(2, 1), nine.position("eDP-1"));
+        assert_eq!(at(1, 1), nine.position("HDMI-A-1"));
+    }
+
+    #[test]
+    fn exec_on_entering_empty_cells() {
+        let mut nine = nine_from(concat!(
+            "rows = 1\n",
+            "cols = 2\n",
+            "cells = [{ num = 1, label = \"web\", exec = \"firefox\" }, 2]\n",
+        ));
+        let web = workspace("1:web", "eDP-1");
+
+        assert_eq!(Some("firefox".into()), nine.exec_on_enter(&web));
+        nine.moved_to(web.clone());
+        assert_eq!("T_", position(&nine));
+        assert_eq!("web", nine.values()["label"].to_string());
+
+        // Not again while it starts up, however often we come back
+        assert_eq!(None, nine.exec_on_enter(&web));
+        assert_eq!(None, nine.exec_on_enter(&web));
+
+        // Only once it failed
+        nine.go(ExecFailed(web.name.clone()));
+        assert_eq!(Some("firefox".into()), nine.exec_on_enter(&web));
+
+        // Never once it has a window
+        nine.workspaces = vec![WorkspaceState {
+            workspace: web.clone(),
+            windows: 1,
+            ..Default::default()
+        }];
+        assert_eq!(None, nine.exec_on_enter(&web));
+        let two = Workspace {
+            num: Some(2),
+            ..web.clone()
+        };
+        assert_eq!(None, nine.exec_on_enter(&two));
+    }
+
+    #[test]
+    fn split_columns_of_tall_grids() {
+        let mut nine = nine_from(concat!(
+            "rows = 12\n",
+            "cols = 2\n",
+            "cells = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,\n",
+            "    13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24]\n",
+        ));
+        nine.moved_to(workspace("24", "eDP-1"));
+        assert_eq!("_12", position(&nine));
+
+        let texts: Vec<_> = nine
+            .split(Split::Columns)
+            .into_iter()
+            .map(|(_, text, _)| text)
+            .collect();
+        assert_eq!(vec!["_", "12"], texts);
+    }
+
+    #[test]
+    fn off_grid_workspaces() {
+        let mut nine = nine_from("");
+
+        nine.moved_to(workspace("6", "eDP-1"));
+        assert_eq!("__M", position(&nine));
+        nine.moved_to(workspace("9", "eDP-1"));
+        assert_eq!("9", position(&nine));
+        nine.moved_to(workspace("__i3_scratch", "eDP-1"));
+        assert_eq!("__i3_scratch", position(&nine));
+
+        // The next move returns to the last cell on the grid
+        let last = at(1, 2);
+        assert_eq!(Some(last), nine.target(&MoveLeft));
+        nine.go_to("eDP-1", last);
+        assert_eq!("__M", position(&nine));
+        assert_eq!(Some(at(1, 1)), nine.target(&MoveLeft));
     }
 }
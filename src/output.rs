@@ -0,0 +1,300 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_json::json;
+use swaybar_types::Header;
+
+use crate::{
+    bar::{Align, Block},
+    widget::{Value, Values},
+};
+
+/// How the bar is written to stdout, set with `--output <name>` or
+/// `output = "<name>"` at the top of the config.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// `man swaybar-protocol`
+    #[default]
+    Swaybar,
+    /// The i3bar protocol, which needs text in every block
+    I3bar,
+    /// One plain line of text per update, e.g. for tmux status lines
+    Text,
+    /// One line of text with ANSI colors
+    Ansi,
+    /// Lemonbar format strings
+    Lemonbar,
+    /// Json lines for a waybar `custom` module
+    Waybar,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "swaybar" => Ok(Output::Swaybar),
+            "i3bar" => Ok(Output::I3bar),
+            "text" => Ok(Output::Text),
+            "ansi" => Ok(Output::Ansi),
+            "lemonbar" => Ok(Output::Lemonbar),
+            "waybar" => Ok(Output::Waybar),
+            _ => Err(format!("unknown output `{}`", s)),
+        }
+    }
+}
+
+impl Output {
+    /// Whether the bar writes click events to our stdin.
+    pub fn click_events(&self) -> bool {
+        matches!(self, Output::Swaybar | Output::I3bar)
+    }
+
+    pub fn write_header(&self, writer: &mut dyn Write, header: &Header) -> io::Result<()> {
+        if let Output::Swaybar | Output::I3bar = self {
+            writeln!(writer, "{}", serde_json::to_string(header)?)?;
+
+            // Begin infinite json-array of updates
+            writeln!(writer, "[")?;
+        }
+        writer.flush()
+    }
+
+    pub fn write_blocks(
+        &self,
+        writer: &mut dyn Write,
+        blocks: &[(Block, Values)],
+    ) -> io::Result<()> {
+        match self {
+            Output::Swaybar => writeln!(writer, "{},", to_json(blocks.iter().map(|(b, _)| b))?)?,
+            Output::I3bar => {
+                let blocks = blocks
+                    .iter()
+                    .map(|(b, _)| b)
+                    .filter(|b| b.full_text.is_some());
+                writeln!(writer, "{},", to_json(blocks)?)?
+            }
+            Output::Text => writeln!(writer, "{}", to_text(blocks))?,
+            Output::Ansi => writeln!(writer, "{}", to_ansi(blocks))?,
+            Output::Lemonbar => writeln!(writer, "{}", to_lemonbar(blocks))?,
+            Output::Waybar => writeln!(writer, "{}", to_waybar(blocks))?,
+        }
+        writer.flush()
+    }
+}
+
+/// Parse a `#rrggbb` or `#rrggbbaa` color.
+pub fn rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn to_json<'a>(blocks: impl Iterator<Item = &'a Block>) -> serde_json::Result<String> {
+    let blocks = blocks
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!("[{}]", blocks.join(",")))
+}
+
+fn texts(blocks: &[(Block, Values)]) -> impl Iterator<Item = (&Block, &str)> {
+    blocks
+        .iter()
+        .filter_map(|(block, _)| block.full_text.as_deref().map(|text| (block, text)))
+        .filter(|(_, text)| !text.is_empty())
+}
+
+fn to_text(blocks: &[(Block, Values)]) -> String {
+    texts(blocks)
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn to_ansi(blocks: &[(Block, Values)]) -> String {
+    texts(blocks)
+        .map(|(block, text)| {
+            let mut result = String::new();
+            if let Some((r, g, b)) = block.color.as_deref().and_then(rgb) {
+                result.push_str(&format!("\x1b[38;2;{};{};{}m", r, g, b));
+            }
+            if let Some((r, g, b)) = block.background.as_deref().and_then(rgb) {
+                result.push_str(&format!("\x1b[48;2;{};{};{}m", r, g, b));
+            }
+            if block.urgent == Some(true) {
+                result.push_str("\x1b[1m");
+            }
+            if result.is_empty() {
+                return text.to_string();
+            }
+            result.push_str(text);
+            result.push_str("\x1b[0m");
+            result
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn to_lemonbar(blocks: &[(Block, Values)]) -> String {
+    let mut left = Vec::new();
+    let mut center = Vec::new();
+    let mut right = Vec::new();
+
+    for (block, text) in texts(blocks) {
+        // Lemonbar treats `%` as the start of a format block
+        let mut result = text.replace('%', "%%");
+        if let Some(color) = block.color.as_deref().filter(|c| rgb(c).is_some()) {
+            result = format!("%{{F{}}}{}%{{F-}}", color, result);
+        }
+        if let Some(color) = block.background.as_deref().filter(|c| rgb(c).is_some()) {
+            result = format!("%{{B{}}}{}%{{B-}}", color, result);
+        }
+        if let Some(color) = block.border.as_deref().filter(|c| rgb(c).is_some()) {
+            result = format!("%{{U{}}}%{{+u}}{}%{{-u}}%{{U-}}", color, result);
+        }
+        match block.align {
+            Some(Align::Left) => left.push(result),
+            Some(Align::Center) => center.push(result),
+            _ => right.push(result),
+        }
+    }
+
+    format!(
+        "%{{l}}{}%{{c}}{}%{{r}}{}",
+        left.join(" "),
+        center.join(" "),
+        right.join(" ")
+    )
+}
+
+fn to_waybar(blocks: &[(Block, Values)]) -> String {
+    let text = texts(blocks)
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let tooltip = texts(blocks)
+        .map(|(block, text)| match block.widget {
+            Some(ref widget) => format!("{}: {}", widget, text),
+            None => text.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut class: Vec<&str> = texts(blocks)
+        .filter_map(|(block, _)| block.widget.as_deref())
+        .collect();
+    if blocks.iter().any(|(block, _)| block.urgent == Some(true)) {
+        class.push("urgent");
+    }
+
+    // Waybar shows one percentage, so take the first widget that has one
+    let percentage = blocks.iter().find_map(|(_, values)| {
+        values
+            .get("percent")
+            .or_else(|| values.get("capacity"))
+            .and_then(Value::as_int)
+    });
+
+    let mut json = json!({
+        "text": text,
+        "tooltip": tooltip,
+        "class": class,
+    });
+    if let Some(percentage) = percentage {
+        json["percentage"] = percentage.into();
+    }
+    json.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{rgb, Output};
+    use crate::{
+        bar::{Align, Block},
+        widget::{values, Values},
+    };
+
+    fn blocks() -> Vec<(Block, Values)> {
+        let volume = Block {
+            widget: Some("volume".into()),
+            full_text: Some("33%".into()),
+            color: Some("#ff0000".into()),
+            align: Some(Align::Left),
+            urgent: Some(true),
+            ..Default::default()
+        };
+        let time = Block {
+            widget: Some("time".into()),
+            full_text: Some("12:01".into()),
+            ..Default::default()
+        };
+        let empty = Block {
+            widget: Some("battery".into()),
+            ..Default::default()
+        };
+        vec![
+            (volume, values([("percent", 33.into())])),
+            (time, Values::new()),
+            (empty, Values::new()),
+        ]
+    }
+
+    fn line(output: Output) -> String {
+        let mut buf = Vec::new();
+        output.write_blocks(&mut buf, &blocks()).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(Some((255, 0, 16)), rgb("#ff0010"));
+        assert_eq!(Some((255, 0, 16)), rgb("#ff001080"));
+        assert_eq!(None, rgb("ff0010"));
+        assert_eq!(None, rgb("#ff00"));
+        assert_eq!(None, rgb("#gg0010"));
+    }
+
+    #[test]
+    fn json_protocols() {
+        let swaybar: Vec<Value> =
+            serde_json::from_str(line(Output::Swaybar).trim_end_matches(",\n")).unwrap();
+        assert_eq!(3, swaybar.len());
+
+        // i3bar needs full_text in every block
+        let i3bar: Vec<Value> =
+            serde_json::from_str(line(Output::I3bar).trim_end_matches(",\n")).unwrap();
+        assert_eq!(2, i3bar.len());
+    }
+
+    #[test]
+    fn text_lines() {
+        assert_eq!("33% | 12:01\n", line(Output::Text));
+        assert_eq!(
+            "\x1b[38;2;255;0;0m\x1b[1m33%\x1b[0m | 12:01\n",
+            line(Output::Ansi)
+        );
+        assert_eq!(
+            "%{l}%{F#ff0000}33%%%{F-}%{c}%{r}12:01\n",
+            line(Output::Lemonbar)
+        );
+    }
+
+    #[test]
+    fn waybar_module() {
+        let json: Value = serde_json::from_str(&line(Output::Waybar)).unwrap();
+        assert_eq!("33% 12:01", json["text"]);
+        assert_eq!("volume: 33%\ntime: 12:01", json["tooltip"]);
+        assert_eq!(
+            serde_json::json!(["volume", "time", "urgent"]),
+            json["class"]
+        );
+        assert_eq!(33, json["percentage"]);
+    }
+}
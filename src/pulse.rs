@@ -9,13 +9,24 @@ use zvariant::ObjectPath;
 
 use crate::bar::Update;
 use crate::err::Res;
-
-pub async fn watch_pulse(tx: UnboundedSender<Update>) -> Res<()> {
+use crate::signal::Paused;
+
+pub async fn watch_pulse(
+    name: String,
+    tx: UnboundedSender<Update>,
+    mut paused: Paused,
+    period: Duration,
+) -> Res<()> {
     loop {
-        tx.send(Update::Volume(get_volume().await.ok()))?;
-        tx.send(Update::Mute(get_mute().await.ok()))?;
+        paused.wait().await;
+        if let Ok(volume) = get_volume().await {
+            tx.send(Update::widget(&name, [("volume", volume.into())]))?;
+        }
+        if let Ok(mute) = get_mute().await {
+            tx.send(Update::widget(&name, [("mute", mute.into())]))?;
+        }
         tx.send(Update::Redraw)?;
-        sleep(Duration::from_secs(5)).await;
+        sleep(period).await;
     }
 }
 
@@ -55,6 +66,7 @@ pub async fn toggle_mute() -> Res<()> {
     Ok(())
 }
 
+#[allow(clippy::get_first)]
 pub async fn get_mute() -> Res<bool> {
     let pulse_conn = new_pulse_connection().await?;
 
@@ -69,6 +81,7 @@ pub async fn get_mute() -> Res<bool> {
     }
 }
 
+#[allow(clippy::get_first)]
 pub async fn get_volume() -> Res<u32> {
     let pulse_conn = new_pulse_connection().await?;
 
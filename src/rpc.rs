@@ -3,10 +3,11 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::from_utf8;
 
+use serde::Deserialize;
 use tokio::io::AsyncReadExt;
 use tokio::net::{UnixListener, UnixStream};
 use tokio::spawn;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, trace};
 
 use crate::{
@@ -20,6 +21,52 @@ use crate::{
     volume::{volume, Volume},
 };
 
+/// Percent changed by each RPC command, configured as e.g. `[steps]` with
+/// `volume_up = 5`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Steps {
+    pub brightness_up: u32,
+    pub brightness_down: u32,
+    pub kbd_up: u32,
+    pub kbd_down: u32,
+    pub volume_up: u32,
+    pub volume_down: u32,
+}
+
+impl Default for Steps {
+    fn default() -> Self {
+        Self {
+            brightness_up: 5,
+            brightness_down: 5,
+            kbd_up: 5,
+            kbd_down: 5,
+            volume_up: 2,
+            volume_down: 2,
+        }
+    }
+}
+
+impl Steps {
+    /// Steps are percents of the full range, so at most 100.
+    pub fn validate(&self) -> Vec<String> {
+        let steps = [
+            ("brightness_up", self.brightness_up),
+            ("brightness_down", self.brightness_down),
+            ("kbd_up", self.kbd_up),
+            ("kbd_down", self.kbd_down),
+            ("volume_up", self.volume_up),
+            ("volume_down", self.volume_down),
+        ];
+
+        steps
+            .iter()
+            .filter(|(_, step)| *step > 100)
+            .map(|(key, step)| format!("steps: `{}` is {}%, at most 100", key, step))
+            .collect()
+    }
+}
+
 pub fn get_socket_path(app_name: &str) -> PathBuf {
     //! Initialize unix socket in system runtime dir
 
@@ -39,7 +86,10 @@ pub fn get_socket_path(app_name: &str) -> PathBuf {
     }
 }
 
-pub async fn watch_rpc(tx: mpsc::UnboundedSender<Update>) -> Res<()> {
+pub async fn watch_rpc(
+    tx: mpsc::UnboundedSender<Update>,
+    steps: watch::Receiver<Steps>,
+) -> Res<()> {
     trace!("Starting get_rpc");
     let sock = get_socket_path("barnine");
     let _ = fs::remove_file(&sock);
@@ -50,37 +100,43 @@ pub async fn watch_rpc(tx: mpsc::UnboundedSender<Update>) -> Res<()> {
 
     loop {
         if let Ok((stream, _addr)) = listener.accept().await {
-            spawn(handle_connection(stream, tx.clone()));
+            let steps = steps.borrow().clone();
+            spawn(handle_connection(stream, tx.clone(), steps));
         }
     }
 }
 
-async fn handle_connection(mut stream: UnixStream, tx: mpsc::UnboundedSender<Update>) -> Res<()> {
+#[allow(clippy::unnecessary_unwrap)]
+async fn handle_connection(
+    mut stream: UnixStream,
+    tx: mpsc::UnboundedSender<Update>,
+    steps: Steps,
+) -> Res<()> {
     let mut buf = vec![0u8; 64];
     if let Ok(len) = stream.read(&mut buf).await {
         if let Ok(msg) = from_utf8(&buf[0..len]) {
             let brightness_delta = match msg {
-                "brightness_up" => Some(Screen(UpPct(5))),
-                "brightness_down" => Some(Screen(DownPct(5))),
-                "kbd_up" => Some(Keyboard(UpPct(5))),
-                "kbd_down" => Some(Keyboard(DownPct(5))),
+                "brightness_up" => Some(Screen(UpPct(steps.brightness_up))),
+                "brightness_down" => Some(Screen(DownPct(steps.brightness_down))),
+                "kbd_up" => Some(Keyboard(UpPct(steps.kbd_up))),
+                "kbd_down" => Some(Keyboard(DownPct(steps.kbd_down))),
                 _ => None,
             };
             if brightness_delta.is_some() {
                 let new_val = brighten(brightness_delta.unwrap()).await?;
-                tx.send(Update::Brightness(Some(new_val)))?;
+                tx.send(Update::widget("brightness", [("percent", new_val.into())]))?;
                 tx.send(Update::Redraw)?;
             }
 
             let volume_delta = match msg {
-                "volume_up" => Some(Volume::Speakers(UpPct(2))),
-                "volume_down" => Some(Volume::Speakers(DownPct(2))),
+                "volume_up" => Some(Volume::Speakers(UpPct(steps.volume_up))),
+                "volume_down" => Some(Volume::Speakers(DownPct(steps.volume_down))),
                 _ => None,
             };
             if volume_delta.is_some() {
                 match volume(volume_delta.unwrap()).await {
                     Ok(new_vol) => {
-                        tx.send(Update::Volume(Some(new_vol)))?;
+                        tx.send(Update::widget("volume", [("volume", new_vol.into())]))?;
                         tx.send(Update::Redraw)?;
                     }
                     Err(err) => {
@@ -90,13 +146,32 @@ async fn handle_connection(mut stream: UnixStream, tx: mpsc::UnboundedSender<Upd
             }
             if let "toggle_mute" = msg {
                 toggle_mute().await.unwrap();
-                tx.send(Update::Mute(Some(get_mute().await.unwrap())))
+                let mute = get_mute().await.unwrap();
+                tx.send(Update::widget("volume", [("mute", mute.into())]))
                     .unwrap();
                 tx.send(Update::Redraw)?;
             }
 
+            // `profile <name>`, or `profile` to go back to the startup layout
+            let mut words = msg.split_whitespace();
+            if let Some("profile") = words.next() {
+                tx.send(Update::Profile(words.next().map(String::from)))?;
+                tx.send(Update::Redraw)?;
+            }
+
             use NineCmd::*;
 
+            // `goto <row> <col>`, counted from 1 at the top left, or
+            // `goto <workspace>` with a workspace number or name of the grid
+            if let Some(("goto", cell)) = msg.split_once(' ') {
+                let cmd = match cell.parse() {
+                    Ok(position) => GoTo(position),
+                    Err(_) => GoToCell(cell.trim().to_string()),
+                };
+                tx.send(Update::Nine(cmd))?;
+                tx.send(Update::Redraw)?;
+            }
+
             if let "move_left" = msg {
                 tx.send(Update::Nine(MoveLeft)).unwrap();
                 tx.send(Update::Redraw)?;
@@ -113,6 +188,60 @@ async fn handle_connection(mut stream: UnixStream, tx: mpsc::UnboundedSender<Upd
                 tx.send(Update::Nine(MoveDown)).unwrap();
                 tx.send(Update::Redraw)?;
             }
+
+            // `page <n>`, counted from 1 like the nine widget shows it
+            let page = match msg.split_once(' ') {
+                Some(("page", n)) => n
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1)),
+                _ => None,
+            };
+            let page = match msg {
+                "page_next" => Some(PageNext),
+                "page_prev" => Some(PagePrev),
+                _ => page.map(Page),
+            };
+            if let Some(page) = page {
+                tx.send(Update::Nine(page))?;
+                tx.send(Update::Redraw)?;
+            }
+
+            let history = match msg {
+                "back" => Some(Back),
+                "forward" => Some(Forward),
+                "last" => Some(Last),
+                _ => None,
+            };
+            if let Some(history) = history {
+                tx.send(Update::Nine(history))?;
+                tx.send(Update::Redraw)?;
+            }
+
+            let carry = match msg {
+                "carry_left" => Some(MoveLeft),
+                "carry_right" => Some(MoveRight),
+                "carry_up" => Some(MoveUp),
+                "carry_down" => Some(MoveDown),
+                _ => None,
+            };
+            if let Some(carry) = carry {
+                tx.send(Update::Carry(carry))?;
+                tx.send(Update::Redraw)?;
+            }
+
+            let swap = match msg {
+                "swap left" => Some(MoveLeft),
+                "swap right" => Some(MoveRight),
+                "swap up" => Some(MoveUp),
+                "swap down" => Some(MoveDown),
+                _ => None,
+            };
+            if let Some(swap) = swap {
+                tx.send(Update::Swap(swap))?;
+                tx.send(Update::Redraw)?;
+            }
         }
     }
     Ok(())
@@ -124,13 +253,26 @@ mod tests {
     use tokio::{
         io::AsyncWriteExt,
         net::UnixStream,
-        sync::mpsc,
+        sync::{mpsc, watch},
         time::{sleep, Duration},
     };
 
-    use super::{get_socket_path, watch_rpc};
+    use super::{get_socket_path, watch_rpc, Steps};
     use crate::bar::Update;
 
+    #[test]
+    fn steps_at_most_100() {
+        let steps = Steps {
+            kbd_down: 101,
+            ..Steps::default()
+        };
+        assert_eq!(
+            vec!["steps: `kbd_down` is 101%, at most 100"],
+            steps.validate()
+        );
+        assert!(Steps::default().validate().is_empty());
+    }
+
     #[tokio::test]
     async fn run_rpc_socket() {
         // Construct expected socket path
@@ -146,7 +288,8 @@ mod tests {
 
         // Start rpc listener task
         let (tx, mut rx) = mpsc::unbounded_channel();
-        tokio::spawn(watch_rpc(tx));
+        let (_steps, rx_steps) = watch::channel(Steps::default());
+        tokio::spawn(watch_rpc(tx, rx_steps));
 
         // Yield for rpc task to start and create socket
         // TODO test fails intermittently..  needs sync on socket file creation
@@ -162,7 +305,7 @@ mod tests {
         let mut got_it = false;
         if let Some(command) = rx.recv().await {
             match command {
-                Update::Volume(Some(_)) => got_it = true,
+                Update::Widget(name, _) if name == "volume" => got_it = true,
                 _ => {}
             }
         }
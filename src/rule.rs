@@ -0,0 +1,170 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use serde::Deserialize;
+
+use crate::{
+    bar::Block,
+    widget::{Value, Values},
+};
+
+/// Block styling applied while a condition on the widget's values holds,
+/// configured per `[[bar]]` entry as e.g.
+/// `rules = [{ when = "capacity < 15", color = "#ff0000", urgent = true }]`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub when: Condition,
+    pub color: Option<String>,
+    pub background: Option<String>,
+    pub border: Option<String>,
+    pub urgent: Option<bool>,
+}
+
+impl Rule {
+    pub fn apply(&self, values: &Values, block: &mut Block) {
+        if !self.when.matches(values) {
+            return;
+        }
+        if self.color.is_some() {
+            block.color = self.color.clone();
+        }
+        if self.background.is_some() {
+            block.background = self.background.clone();
+        }
+        if self.border.is_some() {
+            block.border = self.border.clone();
+        }
+        if self.urgent.is_some() {
+            block.urgent = self.urgent;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// A comparison like `capacity < 15`, `mute == true` or `status != Full`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Condition {
+    pub name: String,
+    pub op: Op,
+    pub value: Value,
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(when: String) -> Result<Self, Self::Error> {
+        // Longer operators first so that `<=` isn't taken for `<`
+        let ops = [
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        let (idx, len, op) = ops
+            .iter()
+            .find_map(|(s, op)| when.find(s).map(|idx| (idx, s.len(), *op)))
+            .ok_or_else(|| format!("no comparison in rule `{}`", when))?;
+
+        let name = when[..idx].trim();
+        let value = when[idx + len..].trim();
+        if name.is_empty() || value.is_empty() {
+            return Err(format!("incomplete rule `{}`", when));
+        }
+
+        let value = if let Ok(val) = value.parse::<i64>() {
+            Value::Int(val)
+        } else if let Ok(val) = value.parse::<bool>() {
+            Value::Bool(val)
+        } else {
+            Value::Text(value.trim_matches(|c| c == '"' || c == '\'').to_string())
+        };
+
+        Ok(Condition {
+            name: name.to_string(),
+            op,
+            value,
+        })
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, values: &Values) -> bool {
+        let ordering = match (values.get(&self.name), &self.value) {
+            (Some(Value::Int(a)), Value::Int(b)) => a.cmp(b),
+            (Some(Value::Bool(a)), Value::Bool(b)) => a.cmp(b),
+            (Some(Value::Text(a)), Value::Text(b)) => a.as_str().cmp(b.as_str()),
+            // Compare against the text of other values, like a numeric status
+            (Some(a), b) => a.to_string().cmp(&b.to_string()),
+            (None, _) => return false,
+        };
+
+        match self.op {
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::{Condition, Op, Rule};
+    use crate::{
+        bar::Block,
+        widget::{values, Value},
+    };
+
+    #[test]
+    fn parse_conditions() {
+        let cond = Condition::try_from("capacity <= 15".to_string()).unwrap();
+        assert_eq!("capacity", cond.name);
+        assert_eq!(Op::Le, cond.op);
+        assert_eq!(Value::Int(15), cond.value);
+
+        let cond = Condition::try_from("mute==true".to_string()).unwrap();
+        assert_eq!(Value::Bool(true), cond.value);
+
+        let cond = Condition::try_from("status != \"Full\"".to_string()).unwrap();
+        assert_eq!(Value::Text("Full".into()), cond.value);
+
+        assert!(Condition::try_from("capacity".to_string()).is_err());
+        assert!(Condition::try_from("< 15".to_string()).is_err());
+    }
+
+    #[test]
+    fn rules_set_block_fields() {
+        let rule: Rule =
+            toml::from_str("when = \"capacity < 15\"\ncolor = \"#ff0000\"\nurgent = true").unwrap();
+        let mut block = Block::default();
+
+        rule.apply(&values([("capacity", 50.into())]), &mut block);
+        assert_eq!(None, block.color);
+
+        rule.apply(&values([("capacity", 5.into())]), &mut block);
+        assert_eq!(Some("#ff0000".into()), block.color);
+        assert_eq!(Some(true), block.urgent);
+
+        // Missing values never match
+        let mut block = Block::default();
+        rule.apply(&values([]), &mut block);
+        assert_eq!(None, block.urgent);
+    }
+}
@@ -0,0 +1,269 @@
+use serde_json::{json, Value};
+
+use crate::widget::Registry;
+
+/// JSON Schema of the config file, for editors that complete `barnine.toml`
+/// through a TOML language server.  Kept by hand in step with `Config` and
+/// `Block`, so the widget names come from the registry.
+pub fn json_schema(registry: &Registry) -> Value {
+    let color = json!({
+        "type": "string",
+        "pattern": "^#([0-9a-fA-F]{6}|[0-9a-fA-F]{8})$",
+    });
+    let action = json!({ "type": "string", "description": "Shell command" });
+    let edge = json!({
+        "description": "What a move past the edge does",
+        "enum": ["wrap", "stop", "spill"],
+    });
+    let grid = json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "rows": { "type": "integer", "minimum": 1 },
+            "cols": { "type": "integer", "minimum": 1 },
+            "cells": {
+                "type": "array",
+                "items": {
+                    "description": "Workspace number or name, or a table with a label",
+                    "type": ["integer", "string", "object"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "num": { "type": "integer" },
+                        "name": { "type": "string" },
+                        "label": {
+                            "description": "Shown by the nine widget, and named after by sway",
+                            "type": "string",
+                        },
+                        "exec": {
+                            "description": "Shell command run on entering the empty workspace",
+                            "type": "string",
+                        },
+                    },
+                },
+            },
+            "horizontal": edge,
+            "vertical": edge,
+            "on_edge": {
+                "description": "Shell command run instead of moves past the edge, with {direction}",
+                "type": "string",
+            },
+            "follow": {
+                "description": "Go along with windows moved by carry_* commands",
+                "type": "boolean",
+            },
+            "start": {
+                "description": "Cell to go to at startup, unless saved from before, counted from 1",
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["row", "col"],
+                "properties": {
+                    "row": { "type": "integer", "minimum": 1 },
+                    "col": { "type": "integer", "minimum": 1 },
+                    "page": { "type": "integer", "minimum": 1 },
+                },
+            },
+            "pages": {
+                "description": "Number of pages of the grid",
+                "type": "integer",
+                "minimum": 1,
+            },
+            "page_offset": {
+                "description": "How much higher workspace numbers are on each page",
+                "type": "integer",
+            },
+            "jump": {
+                "description": "Go to a cell at startup, or stay on the focused workspace",
+                "type": "boolean",
+            },
+        },
+    });
+    let mut nine = grid.clone();
+    nine["description"] = "Workspaces of the nine grid, listed row by row".into();
+    nine["properties"]["outputs"] = json!({
+        "description": "Grids of sway outputs that don't use this one, by name",
+        "type": "object",
+        "additionalProperties": grid,
+    });
+    let block = json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "widget": { "enum": registry.names() },
+            "char_width": { "type": "integer", "minimum": 0 },
+            "format": { "type": "string" },
+            "on_click": { "$ref": "#/definitions/on_click" },
+            "rules": { "type": "array", "items": { "$ref": "#/definitions/rule" } },
+            "interval": {
+                "description": "Seconds between widget updates",
+                "type": "number",
+                "exclusiveMinimum": 0,
+            },
+            "split": {
+                "description": "Show the nine widget as a clickable block per column or cell",
+                "enum": ["columns", "cells"],
+            },
+            "full_text": { "type": "string" },
+            "short_text": { "type": "string" },
+            "background": color,
+            "separator_block_width": { "type": "integer", "minimum": 0 },
+            "min_width": { "type": ["string", "integer"] },
+            "align": { "enum": ["left", "center", "right"] },
+            "color": color,
+            "border": color,
+            "border_top": { "type": "integer", "minimum": 0 },
+            "border_bottom": { "type": "integer", "minimum": 0 },
+            "border_left": { "type": "integer", "minimum": 0 },
+            "border_right": { "type": "integer", "minimum": 0 },
+            "name": { "type": "string" },
+            "instance": { "type": "string" },
+            "urgent": { "type": "boolean" },
+            "separator": { "type": "boolean" },
+            "markup": { "enum": ["none", "pango"] },
+        },
+    });
+    let mut default = block.clone();
+    default["description"] = "Fields used by every block that doesn't set them itself".into();
+    let properties = default["properties"].as_object_mut().unwrap();
+    for key in ["widget", "format", "rules", "on_click", "interval", "split"] {
+        properties.remove(key);
+    }
+    let bar = json!({
+        "description": "Blocks, from left to right",
+        "type": "array",
+        "items": { "$ref": "#/definitions/block" },
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "barnine config",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "include": {
+                "description": "Files to merge in first, relative to this one",
+                "type": "array",
+                "items": { "type": "string" },
+            },
+            "output": {
+                "description": "How the bar is written to stdout",
+                "enum": ["swaybar", "i3bar", "text", "ansi", "lemonbar", "waybar"],
+            },
+            "signals": { "$ref": "#/definitions/signals" },
+            "nine": nine,
+            "minimap": {
+                "description": "Symbols of the minimap widget",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "empty": { "type": "string" },
+                    "occupied": { "type": "string" },
+                    "focused": { "type": "string" },
+                    "urgent": { "type": "string" },
+                    "separator": { "type": "string" },
+                },
+            },
+            "steps": {
+                "description": "Percent changed by each barninec command",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "brightness_up": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "brightness_down": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "kbd_up": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "kbd_down": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "volume_up": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "volume_down": { "type": "integer", "minimum": 0, "maximum": 100 },
+                },
+            },
+            "default": default,
+            "bar": bar,
+            "profile": {
+                "description": "Profile shown at startup",
+                "type": "string",
+            },
+            "profiles": {
+                "description": "Named layouts, switched with `barninec profile <name>`",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": { "default": default, "bar": bar },
+                },
+            },
+        },
+        "definitions": {
+            "signal": {
+                "description": "Signal name like SIGUSR1 or SIGRTMIN+2, or number, but not SIGSTOP or SIGKILL",
+                "type": ["string", "integer"],
+            },
+            "signals": {
+                "type": "object",
+                "additionalProperties": false,
+                "dependencies": { "stop": ["cont"], "cont": ["stop"] },
+                "properties": {
+                    "stop": { "$ref": "#/definitions/signal" },
+                    "cont": { "$ref": "#/definitions/signal" },
+                },
+            },
+            "rule": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["when"],
+                "properties": {
+                    "when": {
+                        "description": "Condition like `capacity < 15`",
+                        "type": "string",
+                        "pattern": "^\\s*\\w+\\s*(<=|>=|==|!=|<|>)",
+                    },
+                    "color": color,
+                    "background": color,
+                    "border": color,
+                    "urgent": { "type": "boolean" },
+                },
+            },
+            "on_click": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "left": action,
+                    "middle": action,
+                    "right": action,
+                    "wheel_up": action,
+                    "wheel_down": action,
+                    "wheel_left": action,
+                    "wheel_right": action,
+                },
+            },
+            "block": block,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::json_schema;
+    use crate::widget::Registry;
+
+    #[test]
+    fn schema_covers_example_config() {
+        let schema = json_schema(&Registry::default());
+        let block = &schema["definitions"]["block"]["properties"];
+        let example: Value = toml::from_str(include_str!("../examples/barnine.toml")).unwrap();
+
+        for (key, _) in example.as_object().unwrap() {
+            assert!(schema["properties"].get(key).is_some(), "{}", key);
+        }
+        let blocks = example["bar"].as_array().unwrap().iter();
+        for block_config in blocks.chain([&example["default"]]) {
+            for (key, _) in block_config.as_object().unwrap() {
+                assert!(block.get(key).is_some(), "{}", key);
+            }
+        }
+        assert!(block["widget"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&"window_name".into()));
+    }
+}
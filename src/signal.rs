@@ -0,0 +1,222 @@
+use futures::{future::BoxFuture, FutureExt};
+use serde::{de, Deserialize, Deserializer};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use tracing::{debug, trace};
+
+use crate::{bar::Update, err::Res};
+
+/// Signals that swaybar sends when the bar is hidden and shown again,
+/// configured as e.g. `[signals]` with `stop = "SIGUSR1"` and `cont = "SIGUSR2"`.
+/// Without them swaybar uses SIGSTOP and SIGCONT, which can't be handled.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signals {
+    pub stop: Option<Signal>,
+    pub cont: Option<Signal>,
+}
+
+impl Signals {
+    /// The signals to use, which only work as a pair: swaybar would stop or
+    /// never resume the bar with just one of them.
+    pub fn pair(&self) -> Option<(Signal, Signal)> {
+        Some((self.stop?, self.cont?))
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.stop.is_some() != self.cont.is_some() {
+            errors.push("signals: set both `stop` and `cont`, or neither".to_string());
+        }
+        if self.stop == Some(Signal(libc::SIGCONT)) {
+            errors.push("signals: `stop` can't be SIGCONT".to_string());
+        }
+        errors
+    }
+}
+
+/// A unix signal number, written in the config by name or number.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Signal(pub i32);
+
+impl Signal {
+    /// A signal that barnine can catch, so not SIGKILL or SIGSTOP.
+    pub fn new(num: i32) -> Option<Self> {
+        match num {
+            libc::SIGKILL | libc::SIGSTOP => None,
+            _ => Some(Signal(num)),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim_start_matches("SIG");
+        let num = match name {
+            "HUP" => libc::SIGHUP,
+            "INT" => libc::SIGINT,
+            "QUIT" => libc::SIGQUIT,
+            "USR1" => libc::SIGUSR1,
+            "USR2" => libc::SIGUSR2,
+            "ALRM" => libc::SIGALRM,
+            "TERM" => libc::SIGTERM,
+            "CONT" => libc::SIGCONT,
+            "TSTP" => libc::SIGTSTP,
+            "WINCH" => libc::SIGWINCH,
+            "RTMIN" => libc::SIGRTMIN(),
+            _ => match name.strip_prefix("RTMIN+") {
+                Some(offset) => libc::SIGRTMIN() + offset.parse::<i32>().ok()?,
+                None => name.parse().ok()?,
+            },
+        };
+        Signal::new(num)
+    }
+}
+
+impl<'de> Deserialize<'de> for Signal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum NameOrNum {
+            Name(String),
+            Num(i32),
+        }
+
+        match NameOrNum::deserialize(deserializer)? {
+            NameOrNum::Num(num) => Signal::new(num)
+                .ok_or_else(|| de::Error::custom(format!("signal {} can't be caught", num))),
+            NameOrNum::Name(name) => Signal::from_name(&name).ok_or_else(|| {
+                de::Error::custom(format!("unknown or uncatchable signal `{}`", name))
+            }),
+        }
+    }
+}
+
+/// Shared switch that pauses the watchers while the bar is hidden.
+#[derive(Debug)]
+pub struct Pause(watch::Sender<bool>);
+
+impl Default for Pause {
+    fn default() -> Self {
+        Self(watch::channel(false).0)
+    }
+}
+
+impl Pause {
+    pub fn set(&self, paused: bool) {
+        self.0.send_replace(paused);
+    }
+
+    pub fn subscribe(&self) -> Paused {
+        Paused(self.0.subscribe())
+    }
+}
+
+/// Watcher side of `Pause`.
+#[derive(Clone, Debug)]
+pub struct Paused(watch::Receiver<bool>);
+
+impl Paused {
+    /// Wait until the bar is visible.
+    pub async fn wait(&mut self) {
+        let _ = self.0.wait_for(|paused| !paused).await;
+    }
+}
+
+/// Catch the configured pair of signals, and return the task that passes
+/// them on to the bar.  The handlers are in place on return, so that the
+/// header can tell swaybar about the signals without an early one killing
+/// barnine.
+pub fn watch_signals(
+    tx: UnboundedSender<Update>,
+    signals: &Signals,
+) -> Res<Option<BoxFuture<'static, Res<()>>>> {
+    let (stop, cont) = match signals.pair() {
+        Some(pair) => pair,
+        None => return Ok(None),
+    };
+    let mut stop = signal(SignalKind::from_raw(stop.0))?;
+    let mut cont = signal(SignalKind::from_raw(cont.0))?;
+
+    let watcher = async move {
+        trace!("Start watch_signals");
+        loop {
+            tokio::select! {
+                Some(()) = stop.recv() => {
+                    debug!("Got stop signal");
+                    tx.send(Update::Stop)?;
+                }
+                Some(()) = cont.recv() => {
+                    debug!("Got cont signal");
+                    tx.send(Update::Cont)?;
+                }
+                else => return Ok(()),
+            }
+        }
+    };
+    Ok(Some(watcher.boxed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+    use tokio::time::{timeout, Duration};
+
+    use super::{watch_signals, Pause, Signal, Signals};
+    use crate::bar::Update;
+
+    #[test]
+    fn signals_from_toml() {
+        let signals: Signals = toml::from_str("stop = \"SIGUSR1\"\ncont = 12").unwrap();
+        assert_eq!(Some(Signal(libc::SIGUSR1)), signals.stop);
+        assert_eq!(Some(Signal(12)), signals.cont);
+
+        let rtmin = Signal::from_name("SIGRTMIN+2").unwrap();
+        assert_eq!(libc::SIGRTMIN() + 2, rtmin.0);
+
+        assert!(toml::from_str::<Signals>("stop = \"SIGNOPE\"").is_err());
+    }
+
+    #[test]
+    fn uncatchable_and_lone_signals() {
+        assert_eq!(None, Signal::from_name("SIGSTOP"));
+        assert_eq!(None, Signal::from_name("KILL"));
+        assert!(toml::from_str::<Signals>("stop = 9").is_err());
+
+        let signals: Signals = toml::from_str("stop = \"SIGUSR1\"").unwrap();
+        assert_eq!(None, signals.pair());
+        assert_eq!(1, signals.validate().len());
+        let signals: Signals = toml::from_str("stop = \"SIGCONT\"\ncont = \"SIGUSR2\"").unwrap();
+        assert_eq!(1, signals.validate().len());
+        let signals: Signals = toml::from_str("stop = \"SIGUSR1\"\ncont = \"SIGUSR2\"").unwrap();
+        assert!(signals.validate().is_empty());
+    }
+
+    #[tokio::test]
+    async fn catch_signals_on_return() {
+        let (tx, mut rx) = unbounded_channel();
+        let signals: Signals = toml::from_str("stop = \"SIGUSR1\"\ncont = \"SIGUSR2\"").unwrap();
+        let watcher = watch_signals(tx.clone(), &signals).unwrap().unwrap();
+        assert!(watch_signals(tx, &Signals::default()).unwrap().is_none());
+
+        // Caught even before the watcher runs
+        unsafe { libc::raise(libc::SIGUSR1) };
+        tokio::spawn(watcher);
+        let update = timeout(Duration::from_secs(1), rx.recv()).await.unwrap();
+        assert!(matches!(update, Some(Update::Stop)));
+    }
+
+    #[tokio::test]
+    async fn pause_blocks_watchers() {
+        let pause = Pause::default();
+        let mut paused = pause.subscribe();
+
+        // Running by default
+        paused.wait().await;
+
+        pause.set(true);
+        let wait = timeout(Duration::from_millis(10), paused.wait()).await;
+        assert!(wait.is_err());
+
+        pause.set(false);
+        paused.wait().await;
+    }
+}
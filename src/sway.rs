@@ -1,13 +1,15 @@
 use swayipc_async::{
-    Connection, Event, EventType, Node, WindowChange, WindowEvent, WorkspaceChange, WorkspaceEvent,
+    Connection, Event, EventType, Node, NodeType, WindowChange, WindowEvent, WorkspaceChange,
+    WorkspaceEvent,
 };
 
-use log::debug;
+use std::env;
+
+use log::{debug, error};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_stream::StreamExt;
 //use tracing::error;
 
-use crate::{bar::Update, nine::NineCmd};
 #[allow(unused_imports)]
 use crate::brightness::{
     brighten,
@@ -15,13 +17,78 @@ use crate::brightness::{
     Delta::{DownPct, UpPct},
 };
 use crate::err::Res;
+use crate::{
+    bar::{Block, Update},
+    format,
+    nine::{NineCmd, Workspace, WorkspaceState},
+    widget::{Values, Widget},
+};
+
+/// Title of the focused window, fed by `watch_sway`.
+#[derive(Debug, Default)]
+pub struct WindowNameWidget(Values);
+
+impl Widget for WindowNameWidget {
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        self.0.clone()
+    }
+
+    fn default_format(&self) -> &str {
+        "{title}"
+    }
+
+    fn render(&self, block: &mut Block) {
+        if !self.0.is_empty() {
+            let template = block
+                .format
+                .as_deref()
+                .unwrap_or_else(|| self.default_format());
+            let window_name = format::render(template, &self.0);
+            let max_chars = block.char_width.unwrap_or(100);
+            let short_window_name = truncate(&window_name, max_chars);
+            let short_window_name = format!("{}*", short_window_name);
+            block.full_text = Some(window_name);
+            block.short_text = Some(short_window_name);
+        }
+    }
+}
+
+fn truncate(s: &str, max_chars: usize) -> &str {
+    match s.char_indices().nth(max_chars) {
+        None => s,
+        Some((idx, _)) => &s[..idx],
+    }
+}
+
+/// Connect to sway, or fail when there's no sway or i3 to ask for its
+/// socket, e.g. for a bar in tmux, where swayipc would panic.
+pub async fn connect() -> Res<Connection> {
+    let has_socket = env::var_os("SWAYSOCK").is_some() || env::var_os("I3SOCK").is_some();
+    let on_path = |wm: &str| {
+        env::var_os("PATH")
+            .map(|path| env::split_paths(&path).any(|dir| dir.join(wm).is_file()))
+            .unwrap_or(false)
+    };
+    if !has_socket && !on_path("sway") && !on_path("i3") {
+        return Err("no sway socket found".into());
+    }
+    Ok(Connection::new().await?)
+}
 
 pub async fn watch_sway(tx: UnboundedSender<Update>) -> Res<()> {
     let subs = [EventType::Window, EventType::Workspace];
-    let mut events = Connection::new().await?.subscribe(&subs).await?;
+    let mut events = connect().await?.subscribe(&subs).await?;
+    let mut sway = connect().await?;
+    send_workspace_states(&mut sway, &tx).await?;
 
     while let Some(event) = events.next().await {
-        match event? {
+        let event = event?;
+        let refresh = changes_workspaces(&event);
+        match event {
             Event::Window(window_event) => match *window_event {
                 WindowEvent {
                     change: WindowChange::Focus,
@@ -43,7 +110,8 @@ pub async fn watch_sway(tx: UnboundedSender<Update>) -> Res<()> {
                     if window_name.is_none() {
                         debug!("Window change with None window_name");
                     }
-                    tx.send(Update::WindowName(Some(window_name.unwrap())))?;
+                    let title = window_name.unwrap_or_default();
+                    tx.send(Update::widget("window_name", [("title", title.into())]))?;
                     tx.send(Update::Redraw)?;
                 }
 
@@ -64,24 +132,27 @@ pub async fn watch_sway(tx: UnboundedSender<Update>) -> Res<()> {
                         Some(Node {
                             nodes: ref _cur_nodes,
                             num: cur_num,
-                            name: _cur_name,
+                            name: cur_name,
+                            output: cur_output,
                             ..
                         }),
                     old:
                         Some(Node {
                             nodes: ref _old_nodes,
                             num: old_num,
-                            name: _old_name,
+                            name: old_name,
                             ..
                         }),
                     ..
                 } = *workspace_event
                 {
-                    if cur_num != old_num {
-                        if let Some(num) = cur_num {
-                            //log::info!("{num} {cur_name:?}");
-                            tx.send(Update::Nine(NineCmd::MovedTo(num)))?;
-                        }
+                    if (cur_num, &cur_name) != (old_num, &old_name) {
+                        let workspace = Workspace {
+                            num: cur_num,
+                            name: cur_name.unwrap_or_default(),
+                            output: cur_output.unwrap_or_default(),
+                        };
+                        tx.send(Update::Nine(NineCmd::MovedTo(workspace)))?;
                     }
 
                     /*if contains_firefox(cur_nodes) {
@@ -104,10 +175,88 @@ pub async fn watch_sway(tx: UnboundedSender<Update>) -> Res<()> {
 
             _ => {}
         }
+
+        // Keep the minimap current
+        if refresh {
+            send_workspace_states(&mut sway, &tx).await?;
+            tx.send(Update::Redraw)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the event can change what `workspace_states` finds, unlike
+/// e.g. a window title changing.
+fn changes_workspaces(event: &Event) -> bool {
+    match event {
+        Event::Workspace(event) => matches!(
+            event.change,
+            WorkspaceChange::Init
+                | WorkspaceChange::Empty
+                | WorkspaceChange::Focus
+                | WorkspaceChange::Move
+                | WorkspaceChange::Rename
+                | WorkspaceChange::Urgent
+                | WorkspaceChange::Reload
+        ),
+        Event::Window(event) => matches!(
+            event.change,
+            WindowChange::New | WindowChange::Close | WindowChange::Move | WindowChange::Urgent
+        ),
+        _ => false,
+    }
+}
+
+/// Send the workspace states, logging rather than failing if sway can't
+/// give its tree.
+async fn send_workspace_states(sway: &mut Connection, tx: &UnboundedSender<Update>) -> Res<()> {
+    match workspace_states(sway).await {
+        Ok(states) => tx.send(Update::Workspaces(states))?,
+        Err(err) => error!("Could not get sway tree: {:?}", err),
     }
     Ok(())
 }
 
+/// What's on each workspace, from the sway tree.
+pub async fn workspace_states(sway: &mut Connection) -> Res<Vec<WorkspaceState>> {
+    let tree = sway.get_tree().await?;
+    let mut states = Vec::new();
+
+    for output in &tree.nodes {
+        for node in &output.nodes {
+            if node.node_type != NodeType::Workspace {
+                continue;
+            }
+            states.push(WorkspaceState {
+                workspace: Workspace {
+                    num: node.num,
+                    name: node.name.clone().unwrap_or_default(),
+                    output: output.name.clone().unwrap_or_default(),
+                },
+                windows: count_windows(node),
+                focused: node.find_focused_as_ref(|n| n.focused).is_some(),
+                urgent: node.urgent,
+            });
+        }
+    }
+
+    Ok(states)
+}
+
+fn count_windows(node: &Node) -> usize {
+    if node.nodes.is_empty() && node.floating_nodes.is_empty() {
+        return match node.node_type {
+            NodeType::Con | NodeType::FloatingCon => 1,
+            _ => 0,
+        };
+    }
+    node.nodes
+        .iter()
+        .chain(node.floating_nodes.iter())
+        .map(count_windows)
+        .sum()
+}
+
 #[allow(dead_code)]
 fn contains_firefox(nodes: &[Node]) -> bool {
     for node in nodes {
@@ -127,9 +276,13 @@ fn contains_firefox(nodes: &[Node]) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::contains_firefox;
-    use crate::bar::Bar;
+    use serde_json::json;
+    use swayipc_async::Event;
+
+    use super::{changes_workspaces, contains_firefox, WindowNameWidget};
+    use crate::bar::Block;
     use crate::tests;
+    use crate::widget::{values, Widget};
 
     const MAX_WINDOW_NAME_LENGTH: usize = 80;
 
@@ -139,6 +292,30 @@ mod tests {
         assert!(contains_firefox(&[node]));
     }
 
+    #[test]
+    fn refresh_on_workspace_changes() {
+        let workspace = |change: &str| {
+            let event = json!({ "change": change, "current": null, "old": null });
+            Event::Workspace(Box::new(serde_json::from_value(event).unwrap()))
+        };
+        let window = |change: &str| {
+            let event = json!({ "change": change, "container": tests::mock_firefox_node() });
+            Event::Window(Box::new(serde_json::from_value(event).unwrap()))
+        };
+
+        for change in [
+            "init", "empty", "focus", "move", "rename", "urgent", "reload",
+        ] {
+            assert!(changes_workspaces(&workspace(change)), "{}", change);
+        }
+        for change in ["new", "close", "move", "urgent"] {
+            assert!(changes_workspaces(&window(change)), "{}", change);
+        }
+        for change in ["title", "focus", "fullscreen_mode", "mark"] {
+            assert!(!changes_workspaces(&window(change)), "{}", change);
+        }
+    }
+
     #[test]
     #[should_panic]
     fn truncate_on_char_boundary() {
@@ -151,12 +328,18 @@ mod tests {
     fn window_name_with_char_boundary() {
         let utf8_3_bytes = "ท";
         let test_str = utf8_3_bytes.repeat(MAX_WINDOW_NAME_LENGTH + 1);
-        let mut display = Bar::default();
-        display.window_name = Some(test_str);
+        let mut widget = WindowNameWidget::default();
+        widget.update(values([("title", test_str.into())]));
 
         // Force truncation on a char boundary
-        let json = display.to_json().unwrap();
+        let mut block = Block {
+            char_width: Some(MAX_WINDOW_NAME_LENGTH),
+            ..Default::default()
+        };
+        widget.render(&mut block);
 
-        assert!(!json.is_empty());
+        let short_text = block.short_text.unwrap();
+        assert_eq!(MAX_WINDOW_NAME_LENGTH + 1, short_text.chars().count());
+        assert!(short_text.ends_with('*'));
     }
 }
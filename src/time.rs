@@ -0,0 +1,135 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::prelude::*;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::{self, Duration, MissedTickBehavior};
+use tracing::warn;
+
+use crate::{
+    bar::{Block, Update},
+    err::Res,
+    signal::Paused,
+    widget::{Value, Values, Watcher, Widget},
+};
+
+/// Local time, with the block `format` taken as a chrono strftime string.
+#[derive(Debug, Default)]
+pub struct TimeWidget(Values);
+
+impl Widget for TimeWidget {
+    fn watch(
+        &self,
+        name: &str,
+        tx: UnboundedSender<Update>,
+        paused: Paused,
+        interval: Option<Duration>,
+    ) -> Option<Watcher> {
+        Some(Box::pin(watch_time(
+            name.to_string(),
+            tx,
+            paused,
+            interval.unwrap_or(Duration::from_secs(1)),
+        )))
+    }
+
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        self.0.clone()
+    }
+
+    fn default_format(&self) -> &str {
+        "%b %d %A %l:%M:%S %p"
+    }
+
+    fn render(&self, block: &mut Block) {
+        let time = self
+            .0
+            .get("timestamp")
+            .and_then(Value::as_int)
+            .and_then(|ts| Local.timestamp_opt(ts, 0).single());
+
+        if let Some(time) = time {
+            let time_format = block
+                .format
+                .as_deref()
+                .unwrap_or_else(|| self.default_format());
+            block.full_text = Some(format_time(&time, time_format));
+        }
+    }
+}
+
+/// Format a time without panicking on bad strftime strings from the config.
+fn format_time(time: &DateTime<Local>, time_format: &str) -> String {
+    let items: Vec<Item> = StrftimeItems::new(time_format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        warn!("Bad time format: {:?}", time_format);
+        return format!("bad format: {}", time_format);
+    }
+    time.format_with_items(items.into_iter()).to_string()
+}
+
+pub async fn watch_time(
+    name: String,
+    tx: UnboundedSender<Update>,
+    mut paused: Paused,
+    period: Duration,
+) -> Res<()> {
+    tracing::trace!("Start watch_time");
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        paused.wait().await;
+
+        let now = Local::now().timestamp();
+        tx.send(Update::widget(&name, [("timestamp", now.into())]))?;
+        tx.send(Update::Redraw)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::TimeWidget;
+    use crate::{
+        bar::{Block, Update},
+        signal::Pause,
+        widget::{values, Widget},
+    };
+
+    #[test]
+    fn strftime_format() {
+        let time = Local.with_ymd_and_hms(2023, 6, 1, 13, 5, 9).unwrap();
+        let mut widget = TimeWidget::default();
+        widget.update(values([("timestamp", time.timestamp().into())]));
+
+        let mut block = Block {
+            format: Some("%H:%M:%S".into()),
+            ..Default::default()
+        };
+        widget.render(&mut block);
+        assert_eq!(Some("13:05:09".into()), block.full_text);
+
+        block.format = Some("%Q".into());
+        widget.render(&mut block);
+        assert!(block.full_text.unwrap().starts_with("bad format"));
+    }
+
+    #[tokio::test]
+    async fn updates_under_registered_name() {
+        let (tx, mut rx) = unbounded_channel();
+        let pause = Pause::default();
+        let watcher = TimeWidget::default()
+            .watch("clock", tx, pause.subscribe(), None)
+            .unwrap();
+        tokio::spawn(watcher);
+
+        let update = rx.recv().await;
+        assert!(matches!(update, Some(Update::Widget(name, _)) if name == "clock"));
+    }
+}
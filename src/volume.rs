@@ -1,6 +1,60 @@
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::trace;
 
-use crate::{brightness::Delta, err::Res, pulse};
+use crate::{
+    bar::Update,
+    brightness::Delta,
+    err::Res,
+    pulse::{self, watch_pulse},
+    signal::Paused,
+    widget::{Value, Values, Watcher, Widget},
+};
+
+/// Speaker volume and mute state from pulseaudio.
+#[derive(Debug, Default)]
+pub struct VolumeWidget(Values);
+
+impl Widget for VolumeWidget {
+    fn watch(
+        &self,
+        name: &str,
+        tx: UnboundedSender<Update>,
+        paused: Paused,
+        interval: Option<Duration>,
+    ) -> Option<Watcher> {
+        Some(Box::pin(watch_pulse(
+            name.to_string(),
+            tx,
+            paused,
+            interval.unwrap_or(Duration::from_secs(5)),
+        )))
+    }
+
+    fn update(&mut self, values: Values) {
+        self.0.extend(values);
+    }
+
+    fn values(&self) -> Values {
+        let mut values = self.0.clone();
+        match values.get("volume").and_then(Value::as_int) {
+            Some(volume) => values.insert("percent".into(), Value::Int(volume * 100 / 65536)),
+            None => return Values::new(),
+        };
+        // TODO test missing fields..
+        let icon = match values.get("mute").and_then(Value::as_bool) {
+            Some(true) => "🔇",
+            _ => "🔈",
+        };
+        values.insert("icon".into(), icon.into());
+        values
+    }
+
+    fn default_format(&self) -> &str {
+        "{percent:>2}{icon}"
+    }
+}
 
 #[derive(Debug)]
 pub enum Volume {
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    bar::{Block, Update},
+    battery::BatteryWidget,
+    brightness::BrightnessWidget,
+    err::Res,
+    format,
+    nine::{MinimapWidget, NineWidget},
+    signal::Paused,
+    sway::WindowNameWidget,
+    time::TimeWidget,
+    volume::VolumeWidget,
+};
+
+/// Named state of a widget, as sent by its watcher.
+pub type Values = BTreeMap<String, Value>;
+
+/// Task that feeds a widget with `Update::Widget` messages.
+pub type Watcher = BoxFuture<'static, Res<()>>;
+
+/// Constructor for a widget, looked up by the `widget` name in the config.
+pub type Factory = fn() -> Box<dyn Widget>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(val) => val.fmt(f),
+            Value::Int(val) => val.fmt(f),
+            Value::Text(val) => val.fmt(f),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Self {
+        Value::Bool(val)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(val: i64) -> Self {
+        Value::Int(val)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(val: i32) -> Self {
+        Value::Int(val.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(val: u32) -> Self {
+        Value::Int(val.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(val: String) -> Self {
+        Value::Text(val)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(val: &str) -> Self {
+        Value::Text(val.to_string())
+    }
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+/// Build widget values from name/value pairs.
+pub fn values<const N: usize>(pairs: [(&str, Value); N]) -> Values {
+    IntoIterator::into_iter(pairs)
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+/// A piece of the bar that owns its state, the task that keeps the state
+/// current, and the rendering of that state into a `Block`.
+pub trait Widget: fmt::Debug + Send {
+    /// Task to spawn when the widget first appears in the config, polling
+    /// every `interval` if the block sets one, or else at its own default.
+    /// Updates go out under `name`, which the widget is registered as.
+    fn watch(
+        &self,
+        _name: &str,
+        _tx: UnboundedSender<Update>,
+        _paused: Paused,
+        _interval: Option<Duration>,
+    ) -> Option<Watcher> {
+        None
+    }
+
+    /// Merge new values into the widget's state.
+    fn update(&mut self, values: Values);
+
+    /// Current state, including any values derived from it.
+    fn values(&self) -> Values;
+
+    /// Template used when the block doesn't set a `format`.
+    fn default_format(&self) -> &str;
+
+    /// Fill in the text of a block configured for this widget.
+    fn render(&self, block: &mut Block) {
+        let values = self.values();
+        if !values.is_empty() {
+            let template = block
+                .format
+                .as_deref()
+                .unwrap_or_else(|| self.default_format());
+            block.full_text = Some(format::render(template, &values));
+        }
+    }
+}
+
+/// Widget constructors keyed by the `widget` name used in `[[bar]]` entries.
+///
+/// Starts out with the builtin widgets.  Other crates can add their own
+/// and run barnine with them through `cli::run`.
+#[derive(Clone, Debug)]
+pub struct Registry(HashMap<String, Factory>);
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self(HashMap::new());
+        registry.register("battery", || Box::<BatteryWidget>::default());
+        registry.register("brightness", || Box::<BrightnessWidget>::default());
+        registry.register("minimap", || Box::<MinimapWidget>::default());
+        registry.register("nine", || Box::<NineWidget>::default());
+        registry.register("time", || Box::<TimeWidget>::default());
+        registry.register("volume", || Box::<VolumeWidget>::default());
+        registry.register("window_name", || Box::<WindowNameWidget>::default());
+        registry
+    }
+}
+
+impl Registry {
+    pub fn register(&mut self, name: &str, factory: Factory) {
+        self.0.insert(name.to_string(), factory);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn create(&self, name: &str) -> Option<Box<dyn Widget>> {
+        self.0.get(name).map(|factory| factory())
+    }
+
+    /// Registered widget names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}